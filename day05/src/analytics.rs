@@ -0,0 +1,181 @@
+/// This file adds overlap queries to the Grid struct, so the overlap
+/// numbers can be used in structured form instead of read off the
+/// printed grid: a histogram of the counts, the cells at or above any
+/// threshold, the hottest cells, per row and per column totals and the
+/// lines covering a cell.
+///
+/// Cells are always returned ordered by row and then column, unless
+/// stated otherwise.
+use std::collections::BTreeMap;
+
+use crate::common::{Coordinate, PointCount};
+use crate::grid::Grid;
+use crate::intersect::segment_contains;
+use crate::line::Orientation;
+
+impl Grid {
+    /// Returns how many cells have each count, including the empty cells
+    /// under count 0 when there are any.
+    pub fn histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        let mut nonzero = 0;
+        for point_count in self.point_counts() {
+            *histogram.entry(point_count.count.0).or_insert(0) += 1;
+            nonzero += 1;
+        }
+        let area = self.width().saturating_mul(self.height());
+        if area > nonzero {
+            histogram.insert(0, area - nonzero);
+        }
+        histogram
+    }
+
+    /// Counts the cells covered by at least k lines.
+    pub fn count_at_least(&self, k: usize) -> usize {
+        self.point_counts()
+            .filter(|point_count| point_count.count.0 >= k.max(1))
+            .count()
+    }
+
+    /// Returns the cells covered by at least k lines.
+    pub fn cells_at_least(&self, k: usize) -> Vec<PointCount> {
+        let mut cells: Vec<_> = self
+            .point_counts()
+            .filter(|point_count| point_count.count.0 >= k.max(1))
+            .collect();
+        cells.sort_by_key(|point_count| (point_count.point.y, point_count.point.x));
+        cells
+    }
+
+    /// Returns the n cells with the highest counts, highest first and
+    /// ties ordered by row and then column.
+    pub fn top_n(&self, n: usize) -> Vec<PointCount> {
+        let mut cells: Vec<_> = self.point_counts().collect();
+        cells.sort_by_key(|point_count| {
+            (
+                std::cmp::Reverse(point_count.count.0),
+                point_count.point.y,
+                point_count.point.x,
+            )
+        });
+        cells.truncate(n);
+        cells
+    }
+
+    /// Returns the sum of the counts of each row (y) with any count.
+    pub fn row_totals(&self) -> BTreeMap<i64, usize> {
+        let mut totals = BTreeMap::new();
+        for point_count in self.point_counts() {
+            *totals.entry(point_count.point.y).or_insert(0) += point_count.count.0;
+        }
+        totals
+    }
+
+    /// Returns the sum of the counts of each column (x) with any count.
+    pub fn column_totals(&self) -> BTreeMap<i64, usize> {
+        let mut totals = BTreeMap::new();
+        for point_count in self.point_counts() {
+            *totals.entry(point_count.point.x).or_insert(0) += point_count.count.0;
+        }
+        totals
+    }
+
+    /// Returns the input indices, see PointsData::indices, of the lines
+    /// drawn on the grid that cover the point.
+    pub fn covering_segments(&self, point: &Coordinate) -> Vec<usize> {
+        let points = self.points();
+        let covers = |start: Coordinate, end: Coordinate| match Orientation::of(&start, &end) {
+            Orientation::Other => self.rasterizer().points(start, end).any(|p| p == *point),
+            _ => segment_contains(start, end, point),
+        };
+        points
+            .indices
+            .iter()
+            .zip(&points.point_pairs)
+            .filter(|(_, (start, end))| points.line_mode.allows(start, end))
+            .filter(|(_, (start, end))| covers(*start, *end))
+            .map(|(&index, _)| index)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{Coordinate, Count, PointCount};
+    use crate::line::LineMode;
+    use crate::testing::sample_grid;
+
+    fn point_count(x: i64, y: i64, count: usize) -> PointCount {
+        PointCount {
+            point: Coordinate { x, y },
+            count: Count(count),
+        }
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = sample_grid(LineMode::Orthogonal).histogram();
+        assert_eq!(histogram.get(&2), Some(&5));
+        assert_eq!(histogram.get(&1), Some(&16));
+        assert_eq!(histogram.values().sum::<usize>(), 100);
+    }
+
+    #[test]
+    fn test_cells_at_least() {
+        let grid = sample_grid(LineMode::Diagonal);
+        assert_eq!(grid.count_at_least(2), 12);
+        assert_eq!(grid.cells_at_least(2).len(), 12);
+        assert_eq!(
+            grid.cells_at_least(3),
+            vec![point_count(4, 4, 3), point_count(6, 4, 3)]
+        );
+        assert_eq!(grid.count_at_least(4), 0);
+    }
+
+    #[test]
+    fn test_top_n() {
+        let grid = sample_grid(LineMode::Diagonal);
+        assert_eq!(
+            grid.top_n(3),
+            vec![
+                point_count(4, 4, 3),
+                point_count(6, 4, 3),
+                point_count(7, 1, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_row_and_column_totals() {
+        let grid = sample_grid(LineMode::Orthogonal);
+        let rows = grid.row_totals();
+        assert_eq!(rows.get(&9), Some(&9));
+        assert_eq!(rows.get(&5), None);
+        let columns = grid.column_totals();
+        assert_eq!(columns.get(&7), Some(&6));
+        assert_eq!(
+            rows.values().sum::<usize>(),
+            columns.values().sum::<usize>()
+        );
+    }
+
+    #[test]
+    fn test_covering_segments() {
+        let diagonal = sample_grid(LineMode::Diagonal);
+        assert_eq!(
+            diagonal.covering_segments(&Coordinate { x: 4, y: 4 }),
+            vec![1, 2, 8]
+        );
+        assert_eq!(
+            diagonal.covering_segments(&Coordinate { x: 9, y: 9 }),
+            vec![]
+        );
+        // Diagonal lines are left out when reading in orthogonal mode,
+        // the lines keep their index in the input.
+        let orthogonal = sample_grid(LineMode::Orthogonal);
+        assert_eq!(
+            orthogonal.covering_segments(&Coordinate { x: 0, y: 9 }),
+            vec![0, 6]
+        );
+    }
+}
@@ -0,0 +1,501 @@
+/// This file parses the command line of the day05 binary and runs it,
+/// so a scenario can be run without editing the source.
+///
+/// The input is read from a file, or from stdin when the path is "-",
+/// and the result is written to the given writer as the overlap count,
+/// the ASCII grid or an image. The diff output compares the input with a
+/// second input, or with itself in another mode.
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use crate::common::{Coordinate, Rect};
+use crate::diff::{render_side_by_side, write_diff_report};
+use crate::file::{read_to_points, PointsData};
+use crate::formats::{
+    read_csv_to_points, read_json_to_points, write_counts_csv, write_counts_json,
+};
+use crate::grid::Grid;
+use crate::image::{write_pgm, write_ppm, ImageOptions};
+use crate::intersect::write_pair_report;
+use crate::line::LineMode;
+use crate::render::{Aggregation, RenderOptions};
+use crate::svg::{write_svg, SvgOptions};
+
+pub const USAGE: &str = "Usage: day05 [INPUT] [OPTIONS]
+
+Arguments:
+  INPUT                  input file, - for stdin [default: data/data1.txt]
+
+Options:
+  -t, --threshold N      count cells covered by at least N lines [default: 2]
+  -f, --format FORMAT    input as text, csv or json [default: text]
+  -m, --mode MODE        orthogonal, diagonal or any [default: orthogonal]
+  -o, --output KIND      count, grid, pgm, ppm, svg, csv, json, pairs or diff
+                         [default: count]
+  -c, --crop X1,Y1,X2,Y2 only use the cells in this rectangle
+  -j, --threads N        draw the lines on N threads [default: 1]
+  -s, --scale N          grid output: one character per N x N cells [default: 1]
+  -a, --aggregate AGG    grid output: max or sum of the scaled cells [default: max]
+  -r, --rulers           grid output: print the coordinates of the cells
+  -d, --diff OTHER       diff output: the input to compare with, - for stdin
+                         [default: INPUT]
+  -M, --diff-mode MODE   diff output: the mode of OTHER [default: MODE]
+  -h, --help             print this help";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Input {
+    Stdin,
+    Path(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Output {
+    Count,
+    Grid,
+    Pgm,
+    Ppm,
+    Svg,
+    Csv,
+    Json,
+    Pairs,
+    Diff,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Options {
+    pub input: Input,
+    pub format: Format,
+    pub threshold: usize,
+    pub line_mode: LineMode,
+    pub output: Output,
+    pub crop: Option<Rect>,
+    pub threads: usize,
+    pub scale: usize,
+    pub aggregation: Aggregation,
+    pub rulers: bool,
+    /// The input compared with by the diff output, None for the same input.
+    pub diff_input: Option<Input>,
+    /// The mode of diff_input, None for the same mode.
+    pub diff_mode: Option<LineMode>,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            input: Input::Path(String::from("data/data1.txt")),
+            format: Format::Text,
+            threshold: 2,
+            line_mode: LineMode::Orthogonal,
+            output: Output::Count,
+            crop: None,
+            threads: 1,
+            scale: 1,
+            aggregation: Aggregation::Max,
+            rulers: false,
+            diff_input: None,
+            diff_mode: None,
+            help: false,
+        }
+    }
+}
+
+fn parse_line_mode(value: &str) -> Result<LineMode, String> {
+    match value {
+        "orthogonal" => Ok(LineMode::Orthogonal),
+        "diagonal" => Ok(LineMode::Diagonal),
+        "any" => Ok(LineMode::Any),
+        _ => Err(format!("unknown mode '{}'", value)),
+    }
+}
+
+fn parse_format(value: &str) -> Result<Format, String> {
+    match value {
+        "text" => Ok(Format::Text),
+        "csv" => Ok(Format::Csv),
+        "json" => Ok(Format::Json),
+        _ => Err(format!("unknown format '{}'", value)),
+    }
+}
+
+fn parse_aggregation(value: &str) -> Result<Aggregation, String> {
+    match value {
+        "max" => Ok(Aggregation::Max),
+        "sum" => Ok(Aggregation::Sum),
+        _ => Err(format!("unknown aggregation '{}'", value)),
+    }
+}
+
+fn parse_output(value: &str) -> Result<Output, String> {
+    match value {
+        "count" => Ok(Output::Count),
+        "grid" => Ok(Output::Grid),
+        "pgm" => Ok(Output::Pgm),
+        "ppm" => Ok(Output::Ppm),
+        "svg" => Ok(Output::Svg),
+        "csv" => Ok(Output::Csv),
+        "json" => Ok(Output::Json),
+        "pairs" => Ok(Output::Pairs),
+        "diff" => Ok(Output::Diff),
+        _ => Err(format!("unknown output '{}'", value)),
+    }
+}
+
+fn parse_crop(value: &str) -> Result<Rect, String> {
+    let numbers = value
+        .split(',')
+        .map(|part| part.trim().parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid crop '{}'", value))?;
+    match numbers[..] {
+        [x1, y1, x2, y2] => Ok(Rect::new(
+            Coordinate { x: x1, y: y1 },
+            Coordinate { x: x2, y: y2 },
+        )),
+        _ => Err(format!("crop '{}' needs four numbers X1,Y1,X2,Y2", value)),
+    }
+}
+
+/// Parses the arguments, without the program name.
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut input = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("missing value for '{}'", arg))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => options.help = true,
+            "-t" | "--threshold" => {
+                let value = value()?;
+                options.threshold = value
+                    .parse()
+                    .ok()
+                    .filter(|&threshold| threshold > 0)
+                    .ok_or_else(|| format!("invalid threshold '{}'", value))?;
+            }
+            "-f" | "--format" => options.format = parse_format(&value()?)?,
+            "-m" | "--mode" => options.line_mode = parse_line_mode(&value()?)?,
+            "-o" | "--output" => options.output = parse_output(&value()?)?,
+            "-c" | "--crop" => options.crop = Some(parse_crop(&value()?)?),
+            "-j" | "--threads" => {
+                let value = value()?;
+                options.threads = value
+                    .parse()
+                    .ok()
+                    .filter(|&threads| threads > 0)
+                    .ok_or_else(|| format!("invalid threads '{}'", value))?;
+            }
+            "-s" | "--scale" => {
+                let value = value()?;
+                options.scale = value
+                    .parse()
+                    .ok()
+                    .filter(|&scale| scale > 0)
+                    .ok_or_else(|| format!("invalid scale '{}'", value))?;
+            }
+            "-a" | "--aggregate" => options.aggregation = parse_aggregation(&value()?)?,
+            "-r" | "--rulers" => options.rulers = true,
+            "-d" | "--diff" => {
+                let value = value()?;
+                options.diff_input = Some(if value == "-" {
+                    Input::Stdin
+                } else {
+                    Input::Path(value)
+                });
+            }
+            "-M" | "--diff-mode" => options.diff_mode = Some(parse_line_mode(&value()?)?),
+            "-" => input = Some(Input::Stdin),
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ if input.is_some() => return Err(format!("unexpected argument '{}'", arg)),
+            _ => input = Some(Input::Path(arg)),
+        }
+    }
+    if let Some(input) = input {
+        options.input = input;
+    }
+    Ok(options)
+}
+
+/// Reads the whole input, from stdin when it is "-".
+fn read_input(input: &Input, stdin: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    match input {
+        Input::Stdin => stdin.read_to_end(&mut bytes)?,
+        Input::Path(path) => File::open(path)?.read_to_end(&mut bytes)?,
+    };
+    Ok(bytes)
+}
+
+fn read_points(bytes: &[u8], format: Format, line_mode: LineMode) -> io::Result<PointsData> {
+    match format {
+        Format::Text => read_to_points(bytes, line_mode),
+        Format::Csv => read_csv_to_points(bytes, line_mode),
+        Format::Json => read_json_to_points(bytes, line_mode),
+    }
+}
+
+fn build_grid(points: PointsData, threads: usize) -> Grid {
+    Grid::builder(points).threads(threads).build()
+}
+
+/// Runs the options, reading stdin when the input is "-". A reader that
+/// stops early and closes the pipe, like head, is not an error.
+pub fn run(options: &Options, stdin: impl Read, writer: impl Write) -> io::Result<()> {
+    match run_output(options, stdin, writer) {
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    }
+}
+
+fn run_output(options: &Options, mut stdin: impl Read, mut writer: impl Write) -> io::Result<()> {
+    if options.help {
+        return writeln!(writer, "{}", USAGE);
+    }
+    let input = read_input(&options.input, &mut stdin)?;
+    let points = read_points(&input, options.format, options.line_mode)?;
+    let grid = build_grid(points, options.threads);
+
+    match options.output {
+        Output::Count => {
+            let count = grid
+                .cells_at_least(options.threshold)
+                .iter()
+                .filter(|point_count| {
+                    options
+                        .crop
+                        .is_none_or(|crop| crop.contains(&point_count.point))
+                })
+                .count();
+            writeln!(writer, "{}", count)
+        }
+        Output::Grid if options.crop.is_none() && options.scale == 1 && !options.rulers => {
+            write!(writer, "{}", grid)
+        }
+        Output::Grid => {
+            let render_options = RenderOptions {
+                viewport: options.crop,
+                rulers: options.rulers,
+                factor: options.scale,
+                aggregation: options.aggregation,
+            };
+            write!(writer, "{}", grid.render(&render_options))
+        }
+        Output::Pgm | Output::Ppm => {
+            let image_options = ImageOptions {
+                threshold: options.threshold,
+                region: options.crop,
+                ..ImageOptions::default()
+            };
+            if options.output == Output::Pgm {
+                write_pgm(&grid, &image_options, writer)
+            } else {
+                write_ppm(&grid, &image_options, writer)
+            }
+        }
+        Output::Svg => {
+            let svg_options = SvgOptions {
+                view_box: options.crop,
+                threshold: options.threshold,
+                ..SvgOptions::default()
+            };
+            write_svg(grid.points(), &grid, &svg_options, writer)
+        }
+        Output::Csv => write_counts_csv(&grid, writer),
+        Output::Json => write_counts_json(&grid, writer),
+        Output::Pairs => write_pair_report(grid.points(), writer),
+        Output::Diff => {
+            // Stdin can only be read once, comparing it with itself reuses the input.
+            let other_input = match &options.diff_input {
+                Some(other) if *other != options.input => read_input(other, &mut stdin)?,
+                _ => input,
+            };
+            let other_mode = options.diff_mode.unwrap_or(options.line_mode);
+            let other = build_grid(
+                read_points(&other_input, options.format, other_mode)?,
+                options.threads,
+            );
+            write_diff_report(&grid.diff(&other, options.threshold), &mut writer)?;
+            writeln!(writer)?;
+            write!(
+                writer,
+                "{}",
+                render_side_by_side(&grid, &other, options.threshold, options.crop)
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::SAMPLE;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn run_input(line: &str, input: &str) -> String {
+        let options = parse_args(args(line)).unwrap();
+        let mut output = Vec::new();
+        run(&options, input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn run_sample(line: &str) -> String {
+        run_input(line, SAMPLE)
+    }
+
+    #[test]
+    fn test_parse_args_defaults() {
+        assert_eq!(parse_args(args("")).unwrap(), Options::default());
+    }
+
+    #[test]
+    fn test_parse_args_all() {
+        let options = parse_args(args("- -t 3 --mode diagonal -o grid --crop 5,5,0,0")).unwrap();
+        assert_eq!(
+            options,
+            Options {
+                input: Input::Stdin,
+                format: Format::Text,
+                threshold: 3,
+                line_mode: LineMode::Diagonal,
+                output: Output::Grid,
+                crop: Some(Rect::new(
+                    Coordinate { x: 0, y: 0 },
+                    Coordinate { x: 5, y: 5 }
+                )),
+                threads: 1,
+                scale: 1,
+                aggregation: Aggregation::Max,
+                rulers: false,
+                diff_input: None,
+                diff_mode: None,
+                help: false,
+            }
+        );
+        let options = parse_args(args("other.json --format json -o csv")).unwrap();
+        assert_eq!(options.input, Input::Path(String::from("other.json")));
+        assert_eq!(options.format, Format::Json);
+        assert_eq!(options.output, Output::Csv);
+        let options = parse_args(args("old.txt -o diff --diff new.txt -M diagonal")).unwrap();
+        assert_eq!(options.output, Output::Diff);
+        assert_eq!(
+            options.diff_input,
+            Some(Input::Path(String::from("new.txt")))
+        );
+        assert_eq!(options.diff_mode, Some(LineMode::Diagonal));
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(parse_args(args("--threshold")).is_err());
+        assert!(parse_args(args("--threshold two")).is_err());
+        assert!(parse_args(args("--threshold 0")).is_err());
+        assert!(parse_args(args("--mode sideways")).is_err());
+        assert!(parse_args(args("--crop 1,2,3")).is_err());
+        assert!(parse_args(args("--verbose")).is_err());
+        assert!(parse_args(args("--threads 0")).is_err());
+        assert!(parse_args(args("--scale 0")).is_err());
+        assert!(parse_args(args("--aggregate avg")).is_err());
+        assert!(parse_args(args("--diff-mode sideways")).is_err());
+        assert!(parse_args(args("a.txt b.txt")).is_err());
+    }
+
+    /// A pipe whose reader is gone.
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::from(io::ErrorKind::BrokenPipe))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_run_closed_pipe() {
+        let options = parse_args(args("- -o grid")).unwrap();
+        assert!(run(&options, SAMPLE.as_bytes(), ClosedPipe).is_ok());
+        let options = parse_args(args("missing.txt")).unwrap();
+        assert!(run(&options, SAMPLE.as_bytes(), ClosedPipe).is_err());
+    }
+
+    #[test]
+    fn test_run_count() {
+        assert_eq!(run_sample("-"), "5\n");
+        assert_eq!(run_sample("- --mode diagonal"), "12\n");
+        assert_eq!(run_sample("- --mode diagonal --threshold 3"), "2\n");
+        assert_eq!(run_sample("- --crop 0,9,9,9"), "3\n");
+        assert_eq!(run_sample("- --mode diagonal --threads 4"), "12\n");
+    }
+
+    #[test]
+    fn test_run_grid() {
+        assert_eq!(
+            run_sample("- --output grid --crop 0,8,4,9"),
+            ".....\n22211\n"
+        );
+        assert!(run_sample("- -o grid").starts_with(".......1..\n..1....1..\n"));
+        assert_eq!(
+            run_sample("- -o grid -m diagonal --scale 5 --aggregate max"),
+            "33\n22\n"
+        );
+        assert!(run_sample("- -o grid --rulers").starts_with("  0\n  |---------\n0 .......1..\n"));
+        assert_eq!(
+            run_input(
+                "- -o grid",
+                "1000000,1000000 -> 1000002,1000000\n1000001,1000000 -> 1000001,1000001\n"
+            ),
+            "origin 1000000,1000000\n121\n.1.\n"
+        );
+    }
+
+    #[test]
+    fn test_run_images() {
+        assert!(run_sample("- -o svg").starts_with("<svg"));
+        let options = parse_args(args("- -o pgm")).unwrap();
+        let mut output = Vec::new();
+        run(&options, SAMPLE.as_bytes(), &mut output).unwrap();
+        assert!(output.starts_with(b"P5\n10 10\n255\n"));
+    }
+
+    #[test]
+    fn test_run_csv_and_json() {
+        let options = parse_args(args("- --format csv -o json")).unwrap();
+        let mut output = Vec::new();
+        run(&options, "0,0,2,0\n1,0,1,1\n".as_bytes(), &mut output).unwrap();
+        let json = String::from_utf8(output).unwrap();
+        assert!(json.contains(r#"{"x":1,"y":0,"count":2}"#));
+        assert!(run_sample("- -o pairs").starts_with("Lines 1 (0,9 -> 5,9) and 7 (0,9 -> 2,9):\n"));
+        assert_eq!(
+            run_input("- -o pairs", "0,0 -> 1,1\n0,0 -> 0,3\n0,0 -> 0,1\n"),
+            "Lines 2 (0,0 -> 0,3) and 3 (0,0 -> 0,1):\n  Run (0,0) -> (0,1)\n  \
+             Point (0,0): Count=2\n  Point (0,1): Count=2\n"
+        );
+        assert!(run_sample("- -o csv").starts_with("x,y,count\n7,0,1\n2,1,1\n"));
+    }
+
+    #[test]
+    fn test_run_diff() {
+        let output = run_sample("- -o diff -M diagonal --crop 3,3,5,4");
+        assert!(output.starts_with(
+            "Overlap delta: +7\nChanged cells: 23\nAdded danger cells (count >= 2): 7\n"
+        ));
+        assert!(output.ends_with("\n... | 1.2 | ^.+\n211 | 231 | .+.\n"));
+        let output = run_sample("- -o diff");
+        assert!(output.starts_with("Overlap delta: +0\nChanged cells: 0\n"));
+    }
+}
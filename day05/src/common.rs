@@ -1,23 +1,254 @@
 use std::fmt;
+use std::ops::{Add, AddAssign, Mul, Sub, SubAssign};
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+use crate::error::{ParseError, ParseErrorKind};
+
+/// A point on the grid. Coordinates are ordered by row, then column,
+/// the order the grid is printed in.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub struct Coordinate {
-    pub y: usize,
-    pub x: usize,
+    pub y: i64,
+    pub x: i64,
+}
+
+/// The difference between two coordinates, used to move one.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct Offset {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Offset {
+    pub const UP: Offset = Offset { x: 0, y: -1 };
+    pub const DOWN: Offset = Offset { x: 0, y: 1 };
+    pub const LEFT: Offset = Offset { x: -1, y: 0 };
+    pub const RIGHT: Offset = Offset { x: 1, y: 0 };
+
+    /// The four orthogonal neighbours, clockwise from up.
+    pub const ORTHOGONAL: [Offset; 4] = [Offset::UP, Offset::RIGHT, Offset::DOWN, Offset::LEFT];
+
+    /// All eight neighbours, clockwise from up.
+    pub const ALL: [Offset; 8] = [
+        Offset::UP,
+        Offset { x: 1, y: -1 },
+        Offset::RIGHT,
+        Offset { x: 1, y: 1 },
+        Offset::DOWN,
+        Offset { x: -1, y: 1 },
+        Offset::LEFT,
+        Offset { x: -1, y: -1 },
+    ];
+
+    pub fn new(x: i64, y: i64) -> Self {
+        Self { x, y }
+    }
+
+    /// Returns the offset with each component reduced to -1, 0 or 1.
+    pub fn signum(&self) -> Offset {
+        Offset::new(self.x.signum(), self.y.signum())
+    }
+
+    /// Returns the z component of the cross product, 0 when the offsets are parallel.
+    pub fn cross(&self, other: Offset) -> i64 {
+        self.x * other.y - self.y * other.x
+    }
+}
+
+impl Add for Offset {
+    type Output = Offset;
+
+    fn add(self, other: Offset) -> Offset {
+        Offset::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl Mul<i64> for Offset {
+    type Output = Offset;
+
+    fn mul(self, factor: i64) -> Offset {
+        Offset::new(self.x * factor, self.y * factor)
+    }
+}
+
+impl Add<Offset> for Coordinate {
+    type Output = Coordinate;
+
+    fn add(self, offset: Offset) -> Coordinate {
+        Coordinate {
+            x: self.x + offset.x,
+            y: self.y + offset.y,
+        }
+    }
+}
+
+impl AddAssign<Offset> for Coordinate {
+    fn add_assign(&mut self, offset: Offset) {
+        *self = *self + offset;
+    }
+}
+
+impl Sub<Offset> for Coordinate {
+    type Output = Coordinate;
+
+    fn sub(self, offset: Offset) -> Coordinate {
+        Coordinate {
+            x: self.x - offset.x,
+            y: self.y - offset.y,
+        }
+    }
+}
+
+impl SubAssign<Offset> for Coordinate {
+    fn sub_assign(&mut self, offset: Offset) {
+        *self = *self - offset;
+    }
+}
+
+/// The offset that moves other to self.
+impl Sub for Coordinate {
+    type Output = Offset;
+
+    fn sub(self, other: Coordinate) -> Offset {
+        Offset::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl Coordinate {
+    /// Moves the coordinate by the offset, None if that overflows.
+    pub fn checked_add(&self, offset: Offset) -> Option<Coordinate> {
+        Some(Coordinate {
+            x: self.x.checked_add(offset.x)?,
+            y: self.y.checked_add(offset.y)?,
+        })
+    }
+
+    /// Returns the number of orthogonal steps between the coordinates.
+    pub fn manhattan(&self, other: &Coordinate) -> u64 {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+
+    /// Returns the number of steps between the coordinates when diagonal
+    /// steps are allowed.
+    pub fn chebyshev(&self, other: &Coordinate) -> u64 {
+        self.x.abs_diff(other.x).max(self.y.abs_diff(other.y))
+    }
+
+    /// Iterates over the neighbours inside the bounds, in the order of offsets.
+    pub fn neighbours<'a>(
+        &self,
+        offsets: &'a [Offset],
+        bounds: &Rect,
+    ) -> impl Iterator<Item = Coordinate> + 'a {
+        let (point, bounds) = (*self, *bounds);
+        offsets
+            .iter()
+            .filter_map(move |&offset| point.checked_add(offset))
+            .filter(move |neighbour| bounds.contains(neighbour))
+    }
+
+    /// Iterates over the up to four orthogonal neighbours inside the bounds.
+    pub fn neighbours4(&self, bounds: &Rect) -> impl Iterator<Item = Coordinate> {
+        self.neighbours(&Offset::ORTHOGONAL, bounds)
+    }
+
+    /// Iterates over the up to eight neighbours inside the bounds,
+    /// diagonal ones included.
+    pub fn neighbours8(&self, bounds: &Rect) -> impl Iterator<Item = Coordinate> {
+        self.neighbours(&Offset::ALL, bounds)
+    }
+}
+
+/// Parses a single number, the column in the error is the start of the text.
+fn parse_number(s: &str, column: usize) -> Result<i64, ParseError> {
+    let lead = s.len() - s.trim_start().len();
+    s.trim().parse().map_err(|_| {
+        ParseError::new(
+            1,
+            column + lead,
+            ParseErrorKind::InvalidNumber(s.trim().to_string()),
+        )
+    })
 }
 
 impl FromStr for Coordinate {
-    type Err = std::num::ParseIntError;
+    type Err = ParseError;
 
+    /// Parses "x,y". Errors are reported on line 1 with 1-based columns into s.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<&str> = s.trim().split(',').collect();
-        let col = parts[0].parse()?;
-        let row = parts[1].parse()?;
+        let lead = s.len() - s.trim_start().len();
+        if s.trim().is_empty() {
+            return Err(ParseError::new(1, 1, ParseErrorKind::MissingCoordinate));
+        }
+        let comma = s
+            .find(',')
+            .ok_or_else(|| ParseError::new(1, lead + 1, ParseErrorKind::MissingComma))?;
+        let col = parse_number(&s[..comma], 1)?;
+        let row = parse_number(&s[comma + 1..], comma + 2)?;
         Ok(Coordinate { x: col, y: row })
     }
 }
 
+/// An inclusive rectangle from min to max.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Rect {
+    pub min: Coordinate,
+    pub max: Coordinate,
+}
+
+impl Rect {
+    /// Creates the smallest rectangle containing both corners.
+    pub fn new(a: Coordinate, b: Coordinate) -> Self {
+        Rect {
+            min: Coordinate {
+                x: a.x.min(b.x),
+                y: a.y.min(b.y),
+            },
+            max: Coordinate {
+                x: a.x.max(b.x),
+                y: a.y.max(b.y),
+            },
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        (self.max.x.abs_diff(self.min.x) as usize).saturating_add(1)
+    }
+
+    pub fn height(&self) -> usize {
+        (self.max.y.abs_diff(self.min.y) as usize).saturating_add(1)
+    }
+
+    pub fn contains(&self, point: &Coordinate) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x) && (self.min.y..=self.max.y).contains(&point.y)
+    }
+
+    /// Returns the smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect {
+            min: Coordinate {
+                x: self.min.x.min(other.min.x),
+                y: self.min.y.min(other.min.y),
+            },
+            max: Coordinate {
+                x: self.max.x.max(other.max.x),
+                y: self.max.y.max(other.max.y),
+            },
+        }
+    }
+
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        self.contains(&other.min) && self.contains(&other.max)
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub struct Count(pub usize);
 
@@ -41,3 +272,86 @@ impl fmt::Display for PointCount {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coordinate_offset_arithmetic() {
+        let start = Coordinate { x: 3, y: -2 };
+        let end = Coordinate { x: -1, y: 6 };
+        let offset = end - start;
+        assert_eq!(offset, Offset::new(-4, 8));
+        assert_eq!(start + offset, end);
+        assert_eq!(end - offset, start);
+        assert_eq!(offset.signum(), Offset::new(-1, 1));
+        assert_eq!(start + offset.signum() * 2, Coordinate { x: 1, y: 0 });
+        assert_eq!(Offset::UP.cross(Offset::RIGHT), 1);
+        assert_eq!(offset.cross(offset * 3), 0);
+
+        let mut point = start;
+        point += Offset::DOWN + Offset::RIGHT;
+        assert_eq!(point, Coordinate { x: 4, y: -1 });
+        point -= Offset::RIGHT;
+        assert_eq!(point, Coordinate { x: 3, y: -1 });
+        assert_eq!(
+            Coordinate { x: i64::MAX, y: 0 }.checked_add(Offset::RIGHT),
+            None
+        );
+    }
+
+    #[test]
+    fn test_coordinate_distances() {
+        let a = Coordinate { x: 1, y: 1 };
+        let b = Coordinate { x: -2, y: 5 };
+        assert_eq!(a.manhattan(&b), 7);
+        assert_eq!(a.chebyshev(&b), 4);
+        assert_eq!(b.manhattan(&a), 7);
+        assert_eq!(a.chebyshev(&a), 0);
+    }
+
+    #[test]
+    fn test_coordinate_neighbours() {
+        let bounds = Rect::new(Coordinate { x: 0, y: 0 }, Coordinate { x: 9, y: 9 });
+        let corner = Coordinate { x: 0, y: 0 };
+        assert_eq!(
+            corner.neighbours4(&bounds).collect::<Vec<_>>(),
+            vec![Coordinate { x: 1, y: 0 }, Coordinate { x: 0, y: 1 }]
+        );
+        assert_eq!(
+            corner.neighbours8(&bounds).collect::<Vec<_>>(),
+            vec![
+                Coordinate { x: 1, y: 0 },
+                Coordinate { x: 1, y: 1 },
+                Coordinate { x: 0, y: 1 }
+            ]
+        );
+        let inner = Coordinate { x: 5, y: 5 };
+        assert_eq!(inner.neighbours4(&bounds).count(), 4);
+        assert_eq!(inner.neighbours8(&bounds).count(), 8);
+        assert!(inner
+            .neighbours8(&bounds)
+            .all(|neighbour| inner.chebyshev(&neighbour) == 1));
+    }
+
+    #[test]
+    fn test_coordinate_ord() {
+        let mut points = vec![
+            Coordinate { x: 2, y: 1 },
+            Coordinate { x: 0, y: 2 },
+            Coordinate { x: 1, y: 1 },
+            Coordinate { x: 5, y: 0 },
+        ];
+        points.sort();
+        assert_eq!(
+            points,
+            vec![
+                Coordinate { x: 5, y: 0 },
+                Coordinate { x: 1, y: 1 },
+                Coordinate { x: 2, y: 1 },
+                Coordinate { x: 0, y: 2 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,514 @@
+/// This file counts the overlap points of vent lines whose coordinates
+/// span a huge range, up to u64, without visiting the points one by one.
+///
+/// Horizontal and vertical lines are counted on a compressed grid: the x
+/// and y values where a line starts or stops cut the plane into intervals
+/// with the same count everywhere inside, so each compressed cell is
+/// counted once and multiplied by its width and height.
+///
+/// Diagonal lines are swept one diagonal at a time as 1D intervals. The
+/// points where a diagonal crosses another diagonal or a horizontal or
+/// vertical line are the only ones where the counts mix, and are
+/// corrected one by one. The result is the exact sum_double_counts of a
+/// Grid built from the same lines. Values are kept as i128, so any u64 or
+/// i64 coordinate works. PointsData only holds i64 coordinates, so inputs
+/// with values above i64::MAX are read with CompressedGrid::read.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, BufRead, BufReader, Read};
+
+use crate::error::{ParseError, ParseErrorKind};
+use crate::file::PointsData;
+use crate::line::LineMode;
+
+type Point = (i128, i128);
+
+/// A stretch of a diagonal, from lo to hi in x, covered by count lines.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Run {
+    lo: i128,
+    hi: i128,
+    count: usize,
+}
+
+/// Merges the intervals into runs of the same count, leaving out the gaps.
+fn sweep(intervals: &[(i128, i128)]) -> Vec<Run> {
+    let mut events: Vec<(i128, isize)> = intervals
+        .iter()
+        .flat_map(|&(lo, hi)| [(lo, 1), (hi + 1, -1)])
+        .collect();
+    events.sort_unstable();
+    let mut runs = Vec::new();
+    let mut count = 0;
+    for (i, &(at, change)) in events.iter().enumerate() {
+        count = (count as isize + change) as usize;
+        match events.get(i + 1) {
+            Some(&(next, _)) if next > at && count > 0 => runs.push(Run {
+                lo: at,
+                hi: next - 1,
+                count,
+            }),
+            _ => {}
+        }
+    }
+    runs
+}
+
+/// Returns the count of the run covering x, 0 if there is none.
+fn count_at(runs: &[Run], x: i128) -> usize {
+    let index = runs.partition_point(|run| run.hi < x);
+    runs.get(index)
+        .filter(|run| run.lo <= x)
+        .map_or(0, |run| run.count)
+}
+
+/// Parses the coordinate "x,y" starting at offset in its line, with
+/// values from i64::MIN to u64::MAX.
+fn parse_wide_coordinate(text: &str, offset: usize) -> Result<Point, ParseError> {
+    let comma = text
+        .find(',')
+        .ok_or_else(|| ParseError::new(1, offset + 1, ParseErrorKind::MissingComma))?;
+    let number = |field: &str, column: usize| {
+        let value = field.trim();
+        value
+            .parse::<i128>()
+            .ok()
+            .filter(|value| (i64::MIN as i128..=u64::MAX as i128).contains(value))
+            .ok_or_else(|| {
+                ParseError::new(1, column, ParseErrorKind::InvalidNumber(value.to_string()))
+            })
+    };
+    Ok((
+        number(&text[..comma], offset + 1)?,
+        number(&text[comma + 1..], offset + comma + 2)?,
+    ))
+}
+
+/// Parses a line "x1,y1 -> x2,y2" with values up to u64::MAX.
+fn parse_wide_line(line: &str) -> Result<(Point, Point), ParseError> {
+    let arrow = line
+        .find("->")
+        .ok_or_else(|| ParseError::new(1, 1, ParseErrorKind::MissingArrow))?;
+    Ok((
+        parse_wide_coordinate(&line[..arrow], 0)?,
+        parse_wide_coordinate(&line[arrow + 2..], arrow + 2)?,
+    ))
+}
+
+fn covering(intervals: &HashMap<i128, Vec<(i128, i128)>>, key: i128, at: i128) -> usize {
+    intervals.get(&key).map_or(0, |intervals| {
+        intervals
+            .iter()
+            .filter(|&&(lo, hi)| (lo..=hi).contains(&at))
+            .count()
+    })
+}
+
+/// The lines of PointsData with compressed coordinates.
+#[derive(Debug, Default)]
+pub struct CompressedGrid {
+    /// x intervals of the horizontal lines, by y.
+    horizontal: HashMap<i128, Vec<(i128, i128)>>,
+    /// y intervals of the vertical lines, by x.
+    vertical: HashMap<i128, Vec<(i128, i128)>>,
+    /// Runs of the lines going down to the right, by y - x, along x.
+    diagonal: BTreeMap<i128, Vec<Run>>,
+    /// Runs of the lines going up to the right, by y + x, along x.
+    anti_diagonal: BTreeMap<i128, Vec<Run>>,
+}
+
+impl CompressedGrid {
+    /// Compresses the lines, given as ((x1, y1), (x2, y2)), keeping the ones
+    /// allowed by the line mode. Returns None when a kept line is not
+    /// horizontal, vertical or at 45 degrees.
+    pub fn new<T: Into<i128> + Copy>(
+        pairs: impl IntoIterator<Item = ((T, T), (T, T))>,
+        line_mode: LineMode,
+    ) -> Option<Self> {
+        let mut grid = CompressedGrid::default();
+        let mut diagonal: BTreeMap<i128, Vec<(i128, i128)>> = BTreeMap::new();
+        let mut anti_diagonal: BTreeMap<i128, Vec<(i128, i128)>> = BTreeMap::new();
+        for ((x1, y1), (x2, y2)) in pairs {
+            let (x1, y1, x2, y2) = (x1.into(), y1.into(), x2.into(), y2.into());
+            let (lo_x, hi_x) = (x1.min(x2), x1.max(x2));
+            let (lo_y, hi_y) = (y1.min(y2), y1.max(y2));
+            if y1 == y2 {
+                grid.horizontal.entry(y1).or_default().push((lo_x, hi_x));
+            } else if x1 == x2 {
+                grid.vertical.entry(x1).or_default().push((lo_y, hi_y));
+            } else if hi_x - lo_x == hi_y - lo_y {
+                if line_mode == LineMode::Orthogonal {
+                    continue;
+                }
+                if (x2 - x1).signum() == (y2 - y1).signum() {
+                    diagonal.entry(y1 - x1).or_default().push((lo_x, hi_x));
+                } else {
+                    anti_diagonal.entry(y1 + x1).or_default().push((lo_x, hi_x));
+                }
+            } else if line_mode == LineMode::Any {
+                return None;
+            }
+        }
+        grid.diagonal = diagonal
+            .into_iter()
+            .map(|(key, intervals)| (key, sweep(&intervals)))
+            .collect();
+        grid.anti_diagonal = anti_diagonal
+            .into_iter()
+            .map(|(key, intervals)| (key, sweep(&intervals)))
+            .collect();
+        Some(grid)
+    }
+
+    /// Compresses the lines of the points. Returns None when the line
+    /// mode is Any and a line is not horizontal, vertical or at 45 degrees.
+    pub fn from_points(points: &PointsData) -> Option<Self> {
+        Self::new(
+            points
+                .point_pairs
+                .iter()
+                .map(|(start, end)| ((start.x, start.y), (end.x, end.y))),
+            points.line_mode,
+        )
+    }
+
+    /// Reads lines "x1,y1 -> x2,y2" like read_to_points, but with values
+    /// from i64::MIN to u64::MAX, which don't all fit in a Coordinate.
+    /// Blank lines are skipped and the first malformed line fails with an
+    /// InvalidData error wrapping the ParseError. Returns None like new.
+    pub fn read(reader: impl Read, line_mode: LineMode) -> io::Result<Option<Self>> {
+        let mut pairs = Vec::new();
+        for (index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let pair = parse_wide_line(&line).map_err(|err| {
+                io::Error::new(io::ErrorKind::InvalidData, err.relocate(index + 1, 0))
+            })?;
+            pairs.push(pair);
+        }
+        Ok(Self::new(pairs, line_mode))
+    }
+
+    /// Returns the number of points covered by 2 or more lines, exactly.
+    pub fn sum_double_counts(&self) -> u128 {
+        let mut total = self.orthogonal_double_counts();
+        total += self.diagonal_double_counts(&self.diagonal);
+        total += self.diagonal_double_counts(&self.anti_diagonal);
+
+        for point in self.crossings() {
+            let orthogonal = self.orthogonal_count(point);
+            let down = self.diagonal_count(&self.diagonal, point.1 - point.0, point.0);
+            let up = self.diagonal_count(&self.anti_diagonal, point.1 + point.0, point.0);
+            let diagonal = down + up;
+            // Points on both kinds of diagonal are counted once, whether they
+            // reach 2 together or were counted by each kind already.
+            if down >= 1 && up >= 1 {
+                total += 1;
+                total -= (down >= 2) as u128 + (up >= 2) as u128;
+            }
+            // Points that only reach 2 with the horizontal and vertical lines,
+            // or that were already counted by both parts.
+            if diagonal == 1 && orthogonal == 1 {
+                total += 1;
+            } else if diagonal >= 2 && orthogonal >= 2 {
+                total -= 1;
+            }
+        }
+        total
+    }
+
+    fn orthogonal_count(&self, (x, y): Point) -> usize {
+        covering(&self.horizontal, y, x) + covering(&self.vertical, x, y)
+    }
+
+    fn diagonal_count(&self, runs: &BTreeMap<i128, Vec<Run>>, key: i128, x: i128) -> usize {
+        runs.get(&key).map_or(0, |runs| count_at(runs, x))
+    }
+
+    /// Counts the points covered by 2 or more horizontal and vertical lines
+    /// on the compressed grid, one row of compressed cells at a time.
+    fn orthogonal_double_counts(&self) -> u128 {
+        let mut xs = Vec::new();
+        let mut ys = Vec::new();
+        for (&y, intervals) in &self.horizontal {
+            ys.extend([y, y + 1]);
+            xs.extend(intervals.iter().flat_map(|&(lo, hi)| [lo, hi + 1]));
+        }
+        for (&x, intervals) in &self.vertical {
+            xs.extend([x, x + 1]);
+            ys.extend(intervals.iter().flat_map(|&(lo, hi)| [lo, hi + 1]));
+        }
+        for values in [&mut xs, &mut ys] {
+            values.sort_unstable();
+            values.dedup();
+        }
+        let column = |x: i128| xs.binary_search(&x).unwrap();
+        let row = |y: i128| ys.binary_search(&y).unwrap();
+
+        // Changes to the vertical count of each column, by the row where they happen.
+        let mut vertical_changes: Vec<Vec<(usize, isize)>> = vec![Vec::new(); ys.len()];
+        for (&x, intervals) in &self.vertical {
+            for &(lo, hi) in intervals {
+                vertical_changes[row(lo)].push((column(x), 1));
+                vertical_changes[row(hi + 1)].push((column(x), -1));
+            }
+        }
+        let mut vertical = vec![0isize; xs.len()];
+        let mut total = 0;
+        for j in 0..ys.len().saturating_sub(1) {
+            for &(i, change) in &vertical_changes[j] {
+                vertical[i] += change;
+            }
+            let mut horizontal = vec![0isize; xs.len()];
+            for &(lo, hi) in self.horizontal.get(&ys[j]).into_iter().flatten() {
+                horizontal[column(lo)] += 1;
+                horizontal[column(hi + 1)] -= 1;
+            }
+            let height = (ys[j + 1] - ys[j]) as u128;
+            let mut running = 0;
+            for i in 0..xs.len() - 1 {
+                running += horizontal[i];
+                if running + vertical[i] >= 2 {
+                    total += (xs[i + 1] - xs[i]) as u128 * height;
+                }
+            }
+        }
+        total
+    }
+
+    fn diagonal_double_counts(&self, runs: &BTreeMap<i128, Vec<Run>>) -> u128 {
+        runs.values()
+            .flatten()
+            .filter(|run| run.count >= 2)
+            .map(|run| (run.hi - run.lo + 1) as u128)
+            .sum()
+    }
+
+    /// Returns every point where a diagonal crosses another diagonal or
+    /// a horizontal or vertical line.
+    fn crossings(&self) -> HashSet<Point> {
+        let mut points = HashSet::new();
+        let on = |run: &Run, x: i128| (run.lo..=run.hi).contains(&x);
+        let diagonals = self
+            .diagonal
+            .iter()
+            .map(|(&key, runs)| (key, runs, 1))
+            .chain(
+                self.anti_diagonal
+                    .iter()
+                    .map(|(&key, runs)| (key, runs, -1)),
+            );
+        for (key, runs, slope) in diagonals {
+            // The diagonal is y = slope * x + key.
+            for run in runs {
+                for (&y, intervals) in &self.horizontal {
+                    let x = (y - key) * slope;
+                    if on(run, x) && intervals.iter().any(|&(lo, hi)| (lo..=hi).contains(&x)) {
+                        points.insert((x, y));
+                    }
+                }
+                for (&x, intervals) in &self.vertical {
+                    let y = slope * x + key;
+                    if on(run, x) && intervals.iter().any(|&(lo, hi)| (lo..=hi).contains(&y)) {
+                        points.insert((x, y));
+                    }
+                }
+                if slope == 1 {
+                    for (&other, other_runs) in &self.anti_diagonal {
+                        // x + key == -x + other
+                        if (other - key) % 2 != 0 {
+                            continue;
+                        }
+                        let x = (other - key) / 2;
+                        if on(run, x) && other_runs.iter().any(|other_run| on(other_run, x)) {
+                            points.insert((x, x + key));
+                        }
+                    }
+                }
+            }
+        }
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Coordinate;
+    use crate::file::{read_file_to_points, read_to_points};
+    use crate::grid::Grid;
+    use crate::testing::SAMPLE;
+
+    #[test]
+    fn test_sweep() {
+        assert_eq!(
+            sweep(&[(0, 5), (3, 8), (3, 3), (10, 10)]),
+            vec![
+                Run {
+                    lo: 0,
+                    hi: 2,
+                    count: 1
+                },
+                Run {
+                    lo: 3,
+                    hi: 3,
+                    count: 3
+                },
+                Run {
+                    lo: 4,
+                    hi: 5,
+                    count: 2
+                },
+                Run {
+                    lo: 6,
+                    hi: 8,
+                    count: 1
+                },
+                Run {
+                    lo: 10,
+                    hi: 10,
+                    count: 1
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compressed_matches_grid() {
+        for (line_mode, expected) in [(LineMode::Orthogonal, 5), (LineMode::Diagonal, 12)] {
+            let points = read_to_points(SAMPLE.as_bytes(), line_mode).unwrap();
+            let compressed = CompressedGrid::from_points(&points).unwrap();
+            assert_eq!(compressed.sum_double_counts(), expected);
+        }
+        for line_mode in [LineMode::Orthogonal, LineMode::Diagonal] {
+            let points = read_file_to_points("data/data1.txt", line_mode).unwrap();
+            let compressed = CompressedGrid::from_points(&points).unwrap();
+            let grid = Grid::new(points);
+            assert_eq!(
+                compressed.sum_double_counts(),
+                grid.sum_double_counts() as u128
+            );
+        }
+    }
+
+    #[test]
+    fn test_compressed_crossings() {
+        // Every kind of line through 5,5, and a few that touch it twice.
+        let pairs = [
+            ((0, 5), (9, 5)),
+            ((5, 0), (5, 9)),
+            ((0, 0), (9, 9)),
+            ((0, 10), (10, 0)),
+            ((5, 5), (5, 5)),
+            ((4, 4), (6, 6)),
+            ((2, 2), (2, 8)),
+            ((7, 3), (7, 3)),
+            ((1, 1), (3, 3)),
+            ((8, 2), (2, 8)),
+            ((2, 8), (4, 6)),
+        ];
+        for line_mode in [LineMode::Orthogonal, LineMode::Diagonal] {
+            let points = PointsData::new(
+                pairs
+                    .iter()
+                    .map(|&((x1, y1), (x2, y2))| {
+                        (Coordinate { x: x1, y: y1 }, Coordinate { x: x2, y: y2 })
+                    })
+                    .collect(),
+                line_mode,
+            );
+            let compressed = CompressedGrid::new(pairs, line_mode).unwrap();
+            assert_eq!(
+                compressed.sum_double_counts(),
+                Grid::new(points).sum_double_counts() as u128
+            );
+        }
+    }
+
+    #[test]
+    fn test_compressed_huge_coordinates() {
+        let max = u64::MAX;
+        let pairs = [
+            ((0, 0), (max, 0)),
+            ((max, 0), (0, 0)),
+            ((7, max), (7, 0)),
+            ((0, max), (max, 0)),
+            ((max - 1, 0), (max - 1, 5)),
+        ];
+        let compressed = CompressedGrid::new(pairs, LineMode::Diagonal).unwrap();
+        // The whole row y = 0, plus both vertical lines crossing the diagonal.
+        assert_eq!(compressed.sum_double_counts(), max as u128 + 1 + 2);
+
+        let shifted: Vec<_> = read_to_points(SAMPLE.as_bytes(), LineMode::Diagonal)
+            .unwrap()
+            .point_pairs
+            .iter()
+            .map(|(start, end)| {
+                let shift = |value: i64| max - 10 + value as u64;
+                (
+                    (shift(start.x), shift(start.y)),
+                    (shift(end.x), shift(end.y)),
+                )
+            })
+            .collect();
+        let compressed = CompressedGrid::new(shifted, LineMode::Diagonal).unwrap();
+        assert_eq!(compressed.sum_double_counts(), 12);
+    }
+
+    #[test]
+    fn test_compressed_read_u64() {
+        let input = "18446744073709551615,0 -> 0,0\n\n\
+                     0,0 -> 18446744073709551615,0\n7,0 -> 7,-5\n";
+        let compressed = CompressedGrid::read(input.as_bytes(), LineMode::Orthogonal)
+            .unwrap()
+            .unwrap();
+        assert_eq!(compressed.sum_double_counts(), u64::MAX as u128 + 1);
+
+        let shifted: String = read_to_points(SAMPLE.as_bytes(), LineMode::Diagonal)
+            .unwrap()
+            .point_pairs
+            .iter()
+            .map(|(start, end)| {
+                let shift = |value: i64| u64::MAX - 10 + value as u64;
+                format!(
+                    "{},{} -> {},{}\n",
+                    shift(start.x),
+                    shift(start.y),
+                    shift(end.x),
+                    shift(end.y)
+                )
+            })
+            .collect();
+        let compressed = CompressedGrid::read(shifted.as_bytes(), LineMode::Diagonal)
+            .unwrap()
+            .unwrap();
+        assert_eq!(compressed.sum_double_counts(), 12);
+
+        let error = |input: &str| {
+            let err = CompressedGrid::read(input.as_bytes(), LineMode::Orthogonal).unwrap_err();
+            *err.into_inner().unwrap().downcast::<ParseError>().unwrap()
+        };
+        assert_eq!(
+            error("0,0 -> 0,0\n18446744073709551616,0 -> 0,0"),
+            ParseError::new(
+                2,
+                1,
+                ParseErrorKind::InvalidNumber(String::from("18446744073709551616"))
+            )
+        );
+        assert_eq!(
+            error("1,2 -> 3 4"),
+            ParseError::new(1, 7, ParseErrorKind::MissingComma)
+        );
+        assert_eq!(error("1,2 3,4").kind, ParseErrorKind::MissingArrow);
+    }
+
+    #[test]
+    fn test_compressed_skewed_lines() {
+        let pairs = [((0, 0), (2, 1)), ((0, 0), (0, 3))];
+        assert!(CompressedGrid::new(pairs, LineMode::Any).is_none());
+        let compressed = CompressedGrid::new(pairs, LineMode::Diagonal).unwrap();
+        assert_eq!(compressed.sum_double_counts(), 0);
+    }
+}
@@ -0,0 +1,223 @@
+/// This file compares two Grids, for example the same survey in
+/// orthogonal and diagonal mode, or yesterday's survey and today's.
+///
+/// The grids can have different bounds, a cell outside a grid counts 0.
+/// A GridDiff lists every cell whose count changed, and the danger cells,
+/// covered by at least threshold lines, that were added or removed.
+///
+/// The side by side rendering prints the before grid, the after grid and
+/// a column of changes for each row of the viewport, where '+' and '-'
+/// mark added and removed danger cells, '^' and 'v' a count that went up
+/// or down and '.' an unchanged cell.
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::common::{Coordinate, Rect};
+use crate::grid::Grid;
+use crate::render::cell_char;
+
+/// A cell whose count is different in the two grids.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct CellChange {
+    pub point: Coordinate,
+    pub before: usize,
+    pub after: usize,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GridDiff {
+    pub threshold: usize,
+    /// Every changed cell, sorted by row and column.
+    pub changed: Vec<CellChange>,
+    /// Cells below the threshold before and at or above it after.
+    pub added: Vec<CellChange>,
+    /// Cells at or above the threshold before and below it after.
+    pub removed: Vec<CellChange>,
+    /// The sum_double_counts of the after grid minus that of the before grid.
+    pub double_counts_delta: i64,
+}
+
+impl Grid {
+    /// Compares this grid, before, with the other grid, after. Danger
+    /// cells are the ones covered by at least threshold lines.
+    pub fn diff(&self, other: &Grid, threshold: usize) -> GridDiff {
+        let mut counts: HashMap<Coordinate, (usize, usize)> = HashMap::new();
+        for point_count in self.point_counts() {
+            counts.entry(point_count.point).or_default().0 = point_count.count.0;
+        }
+        for point_count in other.point_counts() {
+            counts.entry(point_count.point).or_default().1 = point_count.count.0;
+        }
+        let mut changed: Vec<_> = counts
+            .into_iter()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(point, (before, after))| CellChange {
+                point,
+                before,
+                after,
+            })
+            .collect();
+        changed.sort_by_key(|change| change.point);
+
+        let is_danger = |count: usize| count >= threshold;
+        let added = changed
+            .iter()
+            .filter(|change| !is_danger(change.before) && is_danger(change.after))
+            .copied()
+            .collect();
+        let removed = changed
+            .iter()
+            .filter(|change| is_danger(change.before) && !is_danger(change.after))
+            .copied()
+            .collect();
+        GridDiff {
+            threshold,
+            changed,
+            added,
+            removed,
+            double_counts_delta: other.sum_double_counts() as i64 - self.sum_double_counts() as i64,
+        }
+    }
+}
+
+fn write_changes(changes: &[CellChange], mut writer: impl Write) -> io::Result<()> {
+    for change in changes {
+        writeln!(
+            writer,
+            "  Point ({},{}): Count={} -> {}",
+            change.point.x, change.point.y, change.before, change.after
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the summary of the diff, then the added and removed danger cells.
+pub fn write_diff_report(diff: &GridDiff, mut writer: impl Write) -> io::Result<()> {
+    writeln!(writer, "Overlap delta: {:+}", diff.double_counts_delta)?;
+    writeln!(writer, "Changed cells: {}", diff.changed.len())?;
+    writeln!(
+        writer,
+        "Added danger cells (count >= {}): {}",
+        diff.threshold,
+        diff.added.len()
+    )?;
+    write_changes(&diff.added, &mut writer)?;
+    writeln!(
+        writer,
+        "Removed danger cells (count >= {}): {}",
+        diff.threshold,
+        diff.removed.len()
+    )?;
+    write_changes(&diff.removed, &mut writer)
+}
+
+/// Renders the before and after grids and the changes side by side, one
+/// line per row of the viewport. Without a viewport the bounds of both
+/// grids are rendered.
+pub fn render_side_by_side(
+    before: &Grid,
+    after: &Grid,
+    threshold: usize,
+    viewport: Option<Rect>,
+) -> String {
+    let viewport = viewport.unwrap_or(before.bounds().union(&after.bounds()));
+    let count = |grid: &Grid, point: &Coordinate| {
+        grid.get_count(point)
+            .map(|point_count| point_count.count.0)
+            .unwrap_or(0)
+    };
+
+    let mut output = String::new();
+    for y in viewport.min.y..=viewport.max.y {
+        let (mut left, mut right, mut marks) = (String::new(), String::new(), String::new());
+        for x in viewport.min.x..=viewport.max.x {
+            let point = Coordinate { x, y };
+            let (was, is) = (count(before, &point), count(after, &point));
+            left.push(cell_char(was));
+            right.push(cell_char(is));
+            marks.push(match (was >= threshold, is >= threshold) {
+                (false, true) => '+',
+                (true, false) => '-',
+                _ if is > was => '^',
+                _ if is < was => 'v',
+                _ => '.',
+            });
+        }
+        output.push_str(&format!("{} | {} | {}\n", left, right, marks));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line::LineMode;
+    use crate::testing::sample_grid;
+
+    #[test]
+    fn test_diff_orthogonal_to_diagonal() {
+        let (before, after) = (
+            sample_grid(LineMode::Orthogonal),
+            sample_grid(LineMode::Diagonal),
+        );
+        let diff = before.diff(&after, 2);
+        assert_eq!(diff.double_counts_delta, 7);
+        assert_eq!(diff.added.len(), 7);
+        assert!(diff.removed.is_empty());
+        assert!(diff.changed.windows(2).all(|w| w[0].point < w[1].point));
+        assert!(diff.added.contains(&CellChange {
+            point: Coordinate { x: 4, y: 4 },
+            before: 1,
+            after: 3
+        }));
+
+        let reverse = after.diff(&before, 2);
+        assert_eq!(reverse.double_counts_delta, -7);
+        assert_eq!(reverse.removed.len(), 7);
+        assert_eq!(reverse.changed.len(), diff.changed.len());
+        assert_eq!(before.diff(&before, 2).changed, vec![]);
+    }
+
+    #[test]
+    fn test_diff_different_bounds() {
+        let mut before = Grid::empty(LineMode::Orthogonal);
+        before.add_segment(Coordinate { x: 0, y: 0 }, Coordinate { x: 2, y: 0 });
+        before.add_segment(Coordinate { x: 1, y: 0 }, Coordinate { x: 1, y: 1 });
+        let mut after = Grid::empty(LineMode::Orthogonal);
+        after.add_segment(Coordinate { x: 2, y: 0 }, Coordinate { x: 4, y: 0 });
+        after.add_segment(Coordinate { x: 2, y: 0 }, Coordinate { x: 2, y: 1 });
+
+        let diff = before.diff(&after, 2);
+        assert_eq!(diff.double_counts_delta, 0);
+        assert_eq!(
+            diff.added,
+            vec![CellChange {
+                point: Coordinate { x: 2, y: 0 },
+                before: 1,
+                after: 2
+            }]
+        );
+        assert_eq!(
+            diff.removed,
+            vec![CellChange {
+                point: Coordinate { x: 1, y: 0 },
+                before: 2,
+                after: 0
+            }]
+        );
+
+        let mut report = Vec::new();
+        write_diff_report(&diff, &mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "Overlap delta: +0\n\
+             Changed cells: 7\n\
+             Added danger cells (count >= 2): 1\n  Point (2,0): Count=1 -> 2\n\
+             Removed danger cells (count >= 2): 1\n  Point (1,0): Count=2 -> 0\n"
+        );
+        assert_eq!(
+            render_side_by_side(&before, &after, 2, None),
+            "121.. | ..211 | v-+^^\n.1... | ..1.. | .v^..\n"
+        );
+    }
+}
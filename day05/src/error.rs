@@ -0,0 +1,99 @@
+/// This file defines the errors reported while reading vent lines.
+///
+/// A ParseError carries the (1-based) line and column where the problem
+/// was found and a ParseErrorKind with the reason. Errors that only mean
+/// a line was left out, like an empty line or a line not allowed by the
+/// LineMode, are not fatal: they are reported as diagnostics but never
+/// fail a strict read.
+use std::error::Error;
+use std::fmt;
+
+use crate::line::LineMode;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseErrorKind {
+    EmptyLine,
+    MissingArrow,
+    MissingCoordinate,
+    MissingComma,
+    InvalidNumber(String),
+    InvalidRecord(String),
+    UnsupportedLine(LineMode),
+    RaggedRow {
+        expected: usize,
+        found: usize,
+    },
+    InvalidCell(char),
+    /// A '#' cell, printed for a count above 9.
+    CountTooLarge,
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::EmptyLine => write!(f, "empty line"),
+            ParseErrorKind::MissingArrow => write!(f, "expected 'x1,y1 -> x2,y2'"),
+            ParseErrorKind::MissingCoordinate => write!(f, "missing coordinate"),
+            ParseErrorKind::MissingComma => write!(f, "missing ',' in coordinate"),
+            ParseErrorKind::InvalidNumber(text) => write!(f, "invalid number '{}'", text),
+            ParseErrorKind::InvalidRecord(reason) => write!(f, "{}", reason),
+            ParseErrorKind::UnsupportedLine(line_mode) => {
+                write!(f, "line not allowed in {:?} mode", line_mode)
+            }
+            ParseErrorKind::RaggedRow { expected, found } => {
+                write!(f, "row has {} cells, expected {}", found, expected)
+            }
+            ParseErrorKind::InvalidCell(cell) => {
+                write!(f, "invalid cell '{}', expected '.' or a digit", cell)
+            }
+            ParseErrorKind::CountTooLarge => {
+                write!(
+                    f,
+                    "cell '#' holds a count above 9, which can't be read back"
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn new(line: usize, column: usize, kind: ParseErrorKind) -> Self {
+        Self { line, column, kind }
+    }
+
+    /// Returns false for errors that only skip a line.
+    pub fn is_fatal(&self) -> bool {
+        !matches!(
+            self.kind,
+            ParseErrorKind::EmptyLine | ParseErrorKind::UnsupportedLine(_)
+        )
+    }
+
+    /// Moves the error to the given line, shifting the column by offset.
+    pub(crate) fn relocate(self, line: usize, offset: usize) -> Self {
+        Self {
+            line,
+            column: self.column + offset,
+            kind: self.kind,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.kind
+        )
+    }
+}
+
+impl Error for ParseError {}
@@ -8,14 +8,24 @@
 /// Count and PointCount structs to provide a custom format for printing
 /// these types and checking.
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Read};
 
-use crate::common::Coordinate;
+use crate::common::{Coordinate, Rect};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::line::LineMode;
 
+#[derive(Debug)]
 pub struct PointsData {
     pub point_pairs: Vec<(Coordinate, Coordinate)>,
-    pub max_x: usize,
-    pub max_y: usize,
+    /// The index in the input of each point pair, counting the lines left
+    /// out, so results can name the input lines: the line of a text or CSV
+    /// file (from 0) or the position in a JSON array.
+    pub indices: Vec<usize>,
+    pub min_x: i64,
+    pub min_y: i64,
+    pub max_x: i64,
+    pub max_y: i64,
+    pub line_mode: LineMode,
 }
 
 pub struct PointsDataIter<'a> {
@@ -24,7 +34,55 @@ pub struct PointsDataIter<'a> {
 }
 
 impl PointsData {
-    pub fn iter(&self) -> PointsDataIter {
+    /// Creates the points, computing the bounding box of all coordinates.
+    /// The bounding box of no points is the single point 0,0. The point
+    /// pairs are the whole input, with indices from 0.
+    pub fn new(point_pairs: Vec<(Coordinate, Coordinate)>, line_mode: LineMode) -> Self {
+        let indices = (0..point_pairs.len()).collect();
+        Self::with_indices(point_pairs, indices, line_mode)
+    }
+
+    /// Creates the points with the index in the input of each point pair.
+    pub fn with_indices(
+        point_pairs: Vec<(Coordinate, Coordinate)>,
+        indices: Vec<usize>,
+        line_mode: LineMode,
+    ) -> Self {
+        assert_eq!(point_pairs.len(), indices.len(), "one index per point pair");
+        let bounds = point_pairs
+            .iter()
+            .map(|&(start, end)| Rect::new(start, end))
+            .reduce(|a, b| a.union(&b))
+            .unwrap_or(Rect::new(
+                Coordinate { x: 0, y: 0 },
+                Coordinate { x: 0, y: 0 },
+            ));
+        Self {
+            point_pairs,
+            indices,
+            min_x: bounds.min.x,
+            min_y: bounds.min.y,
+            max_x: bounds.max.x,
+            max_y: bounds.max.y,
+            line_mode,
+        }
+    }
+
+    /// Returns the bounding box of all coordinates.
+    pub fn bounds(&self) -> Rect {
+        Rect::new(
+            Coordinate {
+                x: self.min_x,
+                y: self.min_y,
+            },
+            Coordinate {
+                x: self.max_x,
+                y: self.max_y,
+            },
+        )
+    }
+
+    pub fn iter(&self) -> PointsDataIter<'_> {
         PointsDataIter {
             points_data: self,
             index: 0,
@@ -46,40 +104,92 @@ impl<'a> Iterator for PointsDataIter<'a> {
     }
 }
 
-fn parse_line(input: &str) -> Option<(Coordinate, Coordinate)> {
-    let coords: Vec<&str> = input.trim().split("->").collect();
-    if coords.len() != 2 {
-        return None;
+/// Parses a line "x1,y1 -> x2,y2". Errors are reported on line 1 and
+/// lines not allowed by the line mode are reported as UnsupportedLine.
+fn parse_line(input: &str, line_mode: LineMode) -> Result<(Coordinate, Coordinate), ParseError> {
+    let lead = input.len() - input.trim_start().len();
+    if input.trim().is_empty() {
+        return Err(ParseError::new(1, 1, ParseErrorKind::EmptyLine));
+    }
+    let arrow = input
+        .find("->")
+        .ok_or_else(|| ParseError::new(1, lead + 1, ParseErrorKind::MissingArrow))?;
+    let start: Coordinate = input[..arrow].parse()?;
+    let end: Coordinate = input[arrow + 2..]
+        .parse()
+        .map_err(|err: ParseError| err.relocate(1, arrow + 2))?;
+
+    if !line_mode.allows(&start, &end) {
+        return Err(ParseError::new(
+            1,
+            lead + 1,
+            ParseErrorKind::UnsupportedLine(line_mode),
+        ));
     }
-    let start: Coordinate = coords[0].parse().ok()?;
-    let end: Coordinate = coords[1].parse().ok()?;
 
-    if (start.x != end.x) && (start.y != end.y) {
-        return None;
+    Ok((start, end))
+}
+
+/// Reads the lines from the reader, keeping the ones that parse and are
+/// allowed by the line mode. Every skipped line is reported as a
+/// diagnostic. When strict, the first fatal ParseError is returned instead.
+fn read_points(
+    reader: impl BufRead,
+    line_mode: LineMode,
+    strict: bool,
+) -> io::Result<(PointsData, Vec<ParseError>)> {
+    let mut point_pairs = Vec::new();
+    let mut indices = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        match parse_line(&line?, line_mode) {
+            Ok(pair) => {
+                point_pairs.push(pair);
+                indices.push(index);
+            }
+            Err(err) => {
+                let err = err.relocate(index + 1, 0);
+                if strict && err.is_fatal() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+                }
+                diagnostics.push(err);
+            }
+        }
     }
 
-    Some((start, end))
+    Ok((
+        PointsData::with_indices(point_pairs, indices, line_mode),
+        diagnostics,
+    ))
+}
+
+/// Reads the points from any reader, e.g. stdin, a byte slice or a
+/// decompressing reader. Fails with an InvalidData error wrapping the
+/// ParseError on the first malformed line.
+pub fn read_to_points(reader: impl Read, line_mode: LineMode) -> io::Result<PointsData> {
+    read_points(BufReader::new(reader), line_mode, true).map(|(points, _)| points)
+}
+
+/// Reads the points from any reader, skipping malformed lines and
+/// returning a diagnostic for each skipped line next to the points.
+pub fn read_to_points_lenient(
+    reader: impl Read,
+    line_mode: LineMode,
+) -> io::Result<(PointsData, Vec<ParseError>)> {
+    read_points(BufReader::new(reader), line_mode, false)
 }
 
-pub fn read_file_to_points(path: &str) -> io::Result<PointsData> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let point_pairs: Vec<_> = reader
-        .lines()
-        .filter_map(|line| line.ok().as_deref().and_then(parse_line))
-        .collect();
-
-    let (max_x, max_y) = point_pairs
-        .iter()
-        .fold((0, 0), |(max_x, max_y), (start, end)| {
-            (max_x.max(start.x).max(end.x), max_y.max(start.y).max(end.y))
-        });
-
-    Ok(PointsData {
-        point_pairs,
-        max_x,
-        max_y,
-    })
+/// Reads the file, see read_to_points.
+pub fn read_file_to_points(path: &str, line_mode: LineMode) -> io::Result<PointsData> {
+    read_to_points(File::open(path)?, line_mode)
+}
+
+/// Reads the file, see read_to_points_lenient.
+pub fn read_file_to_points_lenient(
+    path: &str,
+    line_mode: LineMode,
+) -> io::Result<(PointsData, Vec<ParseError>)> {
+    read_to_points_lenient(File::open(path)?, line_mode)
 }
 
 #[cfg(test)]
@@ -112,7 +222,7 @@ mod tests {
     #[test]
     fn test_parse_line_ok() {
         let coord_str = String::from("0,9 -> 2,9");
-        let coords = parse_line(&coord_str);
+        let coords = parse_line(&coord_str, LineMode::Orthogonal);
         assert_eq!(
             coords.unwrap(),
             (Coordinate { x: 0, y: 9 }, Coordinate { x: 2, y: 9 })
@@ -122,8 +232,25 @@ mod tests {
     #[test]
     fn test_parse_line_return_none() {
         let coord_str = String::from("0,9 -> 2,8"); // not horizontal or vertical
-        let coords = parse_line(&coord_str);
-        assert!(coords.is_none());
+        let coords = parse_line(&coord_str, LineMode::Orthogonal);
+        assert_eq!(
+            coords.unwrap_err().kind,
+            ParseErrorKind::UnsupportedLine(LineMode::Orthogonal)
+        );
+    }
+
+    #[test]
+    fn test_parse_line_diagonal_mode() {
+        let coord_str = String::from("8,0 -> 0,8");
+        let coords = parse_line(&coord_str, LineMode::Diagonal);
+        assert_eq!(
+            coords.unwrap(),
+            (Coordinate { x: 8, y: 0 }, Coordinate { x: 0, y: 8 })
+        );
+
+        let coord_str = String::from("0,9 -> 2,8"); // not 45 degrees
+        let coords = parse_line(&coord_str, LineMode::Diagonal);
+        assert!(coords.is_err());
     }
 
     #[test]
@@ -133,7 +260,7 @@ mod tests {
         writeln!(file, "0,0 -> 0,1").unwrap();
         writeln!(file, "0,1 -> 1,1").unwrap();
 
-        let points_data = read_file_to_points(path).unwrap();
+        let points_data = read_file_to_points(path, LineMode::Orthogonal).unwrap();
         assert_eq!(points_data.point_pairs.len(), 2);
         assert_eq!(points_data.max_x, 1);
         assert_eq!(points_data.max_y, 1);
@@ -146,7 +273,7 @@ mod tests {
         let path = "/tmp/test_read_file_to_points_empty_file.txt";
         let _ = File::create(path).unwrap();
 
-        let points_data = read_file_to_points(path).unwrap();
+        let points_data = read_file_to_points(path, LineMode::Orthogonal).unwrap();
         assert_eq!(points_data.point_pairs.len(), 0);
         assert_eq!(points_data.max_x, 0);
         assert_eq!(points_data.max_y, 0);
@@ -157,11 +284,88 @@ mod tests {
     #[test]
     fn test_read_file_to_points_nonexistent_file() {
         let path = "/tmp/nonexistent_file.txt";
-        assert!(Path::new(path).exists() == false);
+        assert!(!Path::new(path).exists());
 
-        let result = read_file_to_points(path);
+        let result = read_file_to_points(path, LineMode::Orthogonal);
         assert!(result.is_err());
 
         let _ = std::fs::remove_file(path);
     }
+
+    #[test]
+    fn test_coordinate_from_str_missing_comma() {
+        let err = "09".parse::<Coordinate>().unwrap_err();
+        assert_eq!(err, ParseError::new(1, 1, ParseErrorKind::MissingComma));
+
+        let err = "0,x".parse::<Coordinate>().unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::new(1, 3, ParseErrorKind::InvalidNumber(String::from("x")))
+        );
+    }
+
+    #[test]
+    fn test_parse_line_errors() {
+        let err = parse_line("0,9 2,9", LineMode::Orthogonal).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::MissingArrow);
+
+        let err = parse_line("0,9 -> 29", LineMode::Orthogonal).unwrap_err();
+        assert_eq!(err, ParseError::new(1, 8, ParseErrorKind::MissingComma));
+
+        let err = parse_line("0,9 -> 2,9a", LineMode::Orthogonal).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::new(1, 10, ParseErrorKind::InvalidNumber(String::from("9a")))
+        );
+
+        let err = parse_line("", LineMode::Orthogonal).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::EmptyLine);
+        assert!(!err.is_fatal());
+    }
+
+    #[test]
+    fn test_read_to_points_from_bytes() {
+        let input = "0,9 -> 5,9\n8,0 -> 0,8\n9,4 -> 3,4\n";
+        let points_data = read_to_points(input.as_bytes(), LineMode::Orthogonal).unwrap();
+        assert_eq!(points_data.point_pairs.len(), 2);
+        assert_eq!(points_data.max_x, 9);
+        assert_eq!(points_data.max_y, 9);
+
+        let points_data = read_to_points(input.as_bytes(), LineMode::Diagonal).unwrap();
+        assert_eq!(points_data.point_pairs.len(), 3);
+    }
+
+    #[test]
+    fn test_read_to_points_strict_and_lenient() {
+        let input = "0,0 -> 0,1\n\n0,0 -> 1,1\n0,1 -> 1;1\n";
+
+        let err = read_to_points(input.as_bytes(), LineMode::Orthogonal).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let parse_error = err.get_ref().unwrap().downcast_ref::<ParseError>();
+        assert_eq!(
+            parse_error,
+            Some(&ParseError::new(4, 8, ParseErrorKind::MissingComma))
+        );
+
+        let (points_data, diagnostics) =
+            read_to_points_lenient(input.as_bytes(), LineMode::Orthogonal).unwrap();
+        assert_eq!(points_data.point_pairs.len(), 1);
+        let lines: Vec<_> = diagnostics.iter().map(|err| err.line).collect();
+        assert_eq!(lines, vec![2, 3, 4]);
+        assert_eq!(
+            diagnostics[1].to_string(),
+            "line 3, column 1: line not allowed in Orthogonal mode"
+        );
+    }
+
+    #[test]
+    fn test_read_to_points_negative_bounds() {
+        let input = "-3,2 -> 4,2\n-1,-5 -> -1,0\n";
+        let points_data = read_to_points(input.as_bytes(), LineMode::Orthogonal).unwrap();
+        assert_eq!(points_data.point_pairs.len(), 2);
+        assert_eq!(
+            points_data.bounds(),
+            Rect::new(Coordinate { x: -3, y: -5 }, Coordinate { x: 4, y: 2 })
+        );
+    }
 }
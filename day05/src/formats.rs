@@ -0,0 +1,590 @@
+/// This file imports vent lines from CSV and JSON into PointsData, and
+/// exports the non-zero cells of a Grid as CSV or JSON, so other tools
+/// don't have to read or parse the ASCII grid.
+///
+/// Lines are imported from CSV records "x1,y1,x2,y2", with an optional
+/// header of that form, or from a JSON array of objects with the keys
+/// "x1", "y1", "x2" and "y2". Cells are exported as CSV records
+/// "x,y,count" or as a JSON array of PointCount objects with the keys
+/// "x", "y" and "count".
+///
+/// As with read_to_points, lines not allowed by the LineMode are left out
+/// and malformed input fails with an InvalidData error wrapping a ParseError.
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::common::{Coordinate, PointCount};
+use crate::error::{ParseError, ParseErrorKind};
+use crate::file::PointsData;
+use crate::grid::Grid;
+use crate::line::LineMode;
+
+const CSV_HEADER: &str = "x1,y1,x2,y2";
+
+fn invalid_data(err: ParseError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Keeps the lines allowed by the line mode, each given with its index
+/// in the input.
+fn collect_points(
+    point_pairs: impl IntoIterator<Item = (usize, (Coordinate, Coordinate))>,
+    line_mode: LineMode,
+) -> PointsData {
+    let (indices, point_pairs) = point_pairs
+        .into_iter()
+        .filter(|(_, (start, end))| line_mode.allows(start, end))
+        .unzip();
+    PointsData::with_indices(point_pairs, indices, line_mode)
+}
+
+/// Parses a CSV record "x1,y1,x2,y2" found on the given line.
+fn parse_csv_record(record: &str, line: usize) -> Result<(Coordinate, Coordinate), ParseError> {
+    let mut numbers = Vec::with_capacity(4);
+    let mut column = 1;
+    for field in record.split(',') {
+        let lead = field.len() - field.trim_start().len();
+        let number = field.trim().parse::<i64>().map_err(|_| {
+            ParseError::new(
+                line,
+                column + lead,
+                ParseErrorKind::InvalidNumber(field.trim().to_string()),
+            )
+        })?;
+        numbers.push(number);
+        column += field.len() + 1;
+    }
+    match numbers[..] {
+        [x1, y1, x2, y2] => Ok((Coordinate { x: x1, y: y1 }, Coordinate { x: x2, y: y2 })),
+        _ => Err(ParseError::new(
+            line,
+            1,
+            ParseErrorKind::InvalidRecord(format!("expected 4 fields, found {}", numbers.len())),
+        )),
+    }
+}
+
+/// Reads lines from CSV records "x1,y1,x2,y2". Empty lines are skipped.
+pub fn read_csv_to_points(reader: impl Read, line_mode: LineMode) -> io::Result<PointsData> {
+    let mut point_pairs = Vec::new();
+    for (index, record) in BufReader::new(reader).lines().enumerate() {
+        let record = record?;
+        let trimmed = record.trim();
+        if trimmed.is_empty() || (index == 0 && trimmed.replace(' ', "") == CSV_HEADER) {
+            continue;
+        }
+        point_pairs.push((
+            index,
+            parse_csv_record(&record, index + 1).map_err(invalid_data)?,
+        ));
+    }
+    Ok(collect_points(point_pairs, line_mode))
+}
+
+/// A JSON value with the (1-based) line and column where it starts.
+/// Numbers that aren't integers are only kept as text, the coordinates
+/// of the vent lines must be integers.
+struct Json {
+    line: usize,
+    column: usize,
+    value: JsonValue,
+}
+
+enum JsonValue {
+    Number(i64),
+    /// A fraction, an exponent or an integer that doesn't fit in an i64.
+    OtherNumber(String),
+    /// true, false or null.
+    Literal,
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+struct JsonParser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn error(&self, reason: &str) -> ParseError {
+        ParseError::new(
+            self.line,
+            self.column,
+            ParseErrorKind::InvalidRecord(reason.to_string()),
+        )
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.bump();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&expected) {
+            self.bump();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    /// Parses a comma separated list of items up to the closing char.
+    fn parse_list<T>(
+        &mut self,
+        close: char,
+        mut item: impl FnMut(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Vec<T>, ParseError> {
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&close) {
+            self.bump();
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => continue,
+                Some(c) if c == close => return Ok(items),
+                _ => return Err(self.error(&format!("expected ',' or '{}'", close))),
+            }
+        }
+    }
+
+    /// Reads the 4 hex digits of a \u escape.
+    fn parse_hex4(&mut self) -> Result<u32, ParseError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .bump()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("expected 4 hex digits after \\u"))?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    /// Reads the rest of a \u escape, with the second half of a
+    /// surrogate pair when the first one is a high surrogate.
+    fn parse_unicode_escape(&mut self) -> Result<char, ParseError> {
+        let code = self.parse_hex4()?;
+        let code = match code {
+            0xD800..=0xDBFF => {
+                if self.bump() != Some('\\') || self.bump() != Some('u') {
+                    return Err(self.error("unpaired surrogate in string"));
+                }
+                let low = self.parse_hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.error("unpaired surrogate in string"));
+                }
+                0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+            }
+            0xDC00..=0xDFFF => return Err(self.error("unpaired surrogate in string")),
+            _ => code,
+        };
+        // Every code outside the surrogates is a char.
+        Ok(char::from_u32(code).unwrap())
+    }
+
+    fn parse_string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(text),
+                Some('\\') => match self.bump() {
+                    Some(c @ ('"' | '\\' | '/')) => text.push(c),
+                    Some('b') => text.push('\u{8}'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('n') => text.push('\n'),
+                    Some('r') => text.push('\r'),
+                    Some('t') => text.push('\t'),
+                    Some('u') => text.push(self.parse_unicode_escape()?),
+                    _ => return Err(self.error("unsupported escape in string")),
+                },
+                Some(c) => text.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, ParseError> {
+        self.skip_whitespace();
+        let (line, column) = (self.line, self.column);
+        let value = match self.chars.peek() {
+            Some('[') => {
+                self.bump();
+                JsonValue::Array(self.parse_list(']', Self::parse_value)?)
+            }
+            Some('{') => {
+                self.bump();
+                JsonValue::Object(self.parse_list('}', |parser| {
+                    parser.skip_whitespace();
+                    let key = parser.parse_string()?;
+                    parser.expect(':')?;
+                    Ok((key, parser.parse_value()?))
+                })?)
+            }
+            Some('"') => JsonValue::String(self.parse_string()?),
+            Some(c) if *c == '-' || c.is_ascii_digit() => {
+                let mut text = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !(c == '-' || c.is_ascii_alphanumeric() || c == '.' || c == '+') {
+                        break;
+                    }
+                    text.push(c);
+                    self.bump();
+                }
+                let is_number = text
+                    .chars()
+                    .all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+                    && text.parse::<f64>().is_ok();
+                match text.parse() {
+                    Ok(number) => JsonValue::Number(number),
+                    Err(_) if is_number => JsonValue::OtherNumber(text),
+                    Err(_) => {
+                        return Err(ParseError::new(
+                            line,
+                            column,
+                            ParseErrorKind::InvalidNumber(text),
+                        ))
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let mut word = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !c.is_ascii_alphabetic() {
+                        break;
+                    }
+                    word.push(c);
+                    self.bump();
+                }
+                match word.as_str() {
+                    "true" | "false" | "null" => JsonValue::Literal,
+                    _ => {
+                        return Err(ParseError::new(
+                            line,
+                            column,
+                            ParseErrorKind::InvalidRecord(format!("unexpected '{}'", word)),
+                        ))
+                    }
+                }
+            }
+            Some(_) => return Err(self.error("unexpected character")),
+            None => return Err(self.error("unexpected end of input")),
+        };
+        Ok(Json {
+            line,
+            column,
+            value,
+        })
+    }
+}
+
+/// Turns a JSON object with the keys "x1", "y1", "x2" and "y2" into a line.
+fn json_to_pair(json: &Json) -> Result<(Coordinate, Coordinate), ParseError> {
+    let fields = match &json.value {
+        JsonValue::Object(fields) => fields,
+        _ => {
+            return Err(ParseError::new(
+                json.line,
+                json.column,
+                ParseErrorKind::InvalidRecord(String::from("expected an object")),
+            ))
+        }
+    };
+    let number = |key: &str| {
+        let field = fields.iter().find(|(name, _)| name == key).ok_or_else(|| {
+            ParseError::new(
+                json.line,
+                json.column,
+                ParseErrorKind::InvalidRecord(format!("missing \"{}\"", key)),
+            )
+        })?;
+        let reason = match &field.1.value {
+            JsonValue::Number(number) => return Ok(*number),
+            JsonValue::OtherNumber(text) => {
+                return Err(ParseError::new(
+                    field.1.line,
+                    field.1.column,
+                    ParseErrorKind::InvalidNumber(text.clone()),
+                ))
+            }
+            JsonValue::String(text) => {
+                format!("\"{}\" must be a number, found \"{}\"", key, text)
+            }
+            _ => format!("\"{}\" must be a number", key),
+        };
+        Err(ParseError::new(
+            field.1.line,
+            field.1.column,
+            ParseErrorKind::InvalidRecord(reason),
+        ))
+    };
+    Ok((
+        Coordinate {
+            x: number("x1")?,
+            y: number("y1")?,
+        },
+        Coordinate {
+            x: number("x2")?,
+            y: number("y2")?,
+        },
+    ))
+}
+
+/// Reads lines from a JSON array of objects with the keys "x1", "y1",
+/// "x2" and "y2", whose values must be integers. Other keys are ignored,
+/// whatever their values.
+pub fn read_json_to_points(mut reader: impl Read, line_mode: LineMode) -> io::Result<PointsData> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    let mut parser = JsonParser::new(&input);
+    let json = parser.parse_value().map_err(invalid_data)?;
+    parser.skip_whitespace();
+    if parser.chars.peek().is_some() {
+        return Err(invalid_data(
+            parser.error("unexpected data after the array"),
+        ));
+    }
+    let items = match &json.value {
+        JsonValue::Array(items) => items,
+        _ => {
+            return Err(invalid_data(ParseError::new(
+                json.line,
+                json.column,
+                ParseErrorKind::InvalidRecord(String::from("expected an array")),
+            )))
+        }
+    };
+    let point_pairs = items
+        .iter()
+        .map(json_to_pair)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(invalid_data)?;
+    Ok(collect_points(
+        point_pairs.into_iter().enumerate(),
+        line_mode,
+    ))
+}
+
+/// Writes the non-zero cells of the grid as "x,y,count" records with a
+/// header, ordered by row and then column.
+pub fn write_counts_csv(grid: &Grid, mut writer: impl Write) -> io::Result<()> {
+    writeln!(writer, "x,y,count")?;
+    for point_count in grid.cells_at_least(1) {
+        writeln!(
+            writer,
+            "{},{},{}",
+            point_count.point.x, point_count.point.y, point_count.count.0
+        )?;
+    }
+    Ok(())
+}
+
+impl PointCount {
+    /// Formats the point count as a JSON object with the keys "x", "y" and "count".
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"x":{},"y":{},"count":{}}}"#,
+            self.point.x, self.point.y, self.count.0
+        )
+    }
+}
+
+/// Writes the non-zero cells of the grid as a JSON array of PointCount
+/// objects, one per line, ordered by row and then column.
+pub fn write_counts_json(grid: &Grid, mut writer: impl Write) -> io::Result<()> {
+    let cells = grid.cells_at_least(1);
+    writeln!(writer, "[")?;
+    for (index, point_count) in cells.iter().enumerate() {
+        let separator = if index + 1 < cells.len() { "," } else { "" };
+        writeln!(writer, "  {}{}", point_count.to_json(), separator)?;
+    }
+    writeln!(writer, "]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Count;
+
+    fn pair(x1: i64, y1: i64, x2: i64, y2: i64) -> (Coordinate, Coordinate) {
+        (Coordinate { x: x1, y: y1 }, Coordinate { x: x2, y: y2 })
+    }
+
+    fn parse_error(err: io::Error) -> ParseError {
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        err.get_ref()
+            .unwrap()
+            .downcast_ref::<ParseError>()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_read_csv_to_points() {
+        let input = "x1,y1,x2,y2\n0,9,5,9\n\n8,0,0,8\n -1, 2 ,-1,4\n";
+        let points = read_csv_to_points(input.as_bytes(), LineMode::Orthogonal).unwrap();
+        assert_eq!(
+            points.point_pairs,
+            vec![pair(0, 9, 5, 9), pair(-1, 2, -1, 4)]
+        );
+        let points = read_csv_to_points(input.as_bytes(), LineMode::Diagonal).unwrap();
+        assert_eq!(points.point_pairs.len(), 3);
+        assert_eq!(points.min_x, -1);
+    }
+
+    #[test]
+    fn test_read_csv_to_points_errors() {
+        let err = read_csv_to_points("0,9,5,9\n0,9,x,9\n".as_bytes(), LineMode::Orthogonal);
+        assert_eq!(
+            parse_error(err.unwrap_err()),
+            ParseError::new(2, 5, ParseErrorKind::InvalidNumber(String::from("x")))
+        );
+        let err = read_csv_to_points("0,9,5\n".as_bytes(), LineMode::Orthogonal);
+        assert_eq!(
+            parse_error(err.unwrap_err()).to_string(),
+            "line 1, column 1: expected 4 fields, found 3"
+        );
+    }
+
+    #[test]
+    fn test_read_json_to_points() {
+        let input = r#"[
+            {"x1": 0, "y1": 9, "x2": 5, "y2": 9, "name": "a"},
+            {"x1": 8, "y1": 0, "x2": 0, "y2": 8}
+        ]"#;
+        let points = read_json_to_points(input.as_bytes(), LineMode::Diagonal).unwrap();
+        assert_eq!(points.point_pairs, vec![pair(0, 9, 5, 9), pair(8, 0, 0, 8)]);
+        let points = read_json_to_points(input.as_bytes(), LineMode::Orthogonal).unwrap();
+        assert_eq!(points.point_pairs, vec![pair(0, 9, 5, 9)]);
+        let input = r#"[
+            {"x1": 0, "y1": 0, "x2": 0, "y2": 3, "ok": true, "seen": null},
+            {"x1": 1, "y1": 1, "x2": 2, "y2": 2, "ok": false, "depth": -1.5e3, "id": 1e30},
+            {"x1": 3, "y1": 0, "x2": 3, "y2": 1, "note": "a\rb\u00e9\b\f\ud83d\ude00"}
+        ]"#;
+        let points = read_json_to_points(input.as_bytes(), LineMode::Diagonal).unwrap();
+        assert_eq!(
+            points.point_pairs,
+            vec![pair(0, 0, 0, 3), pair(1, 1, 2, 2), pair(3, 0, 3, 1)]
+        );
+        let points = read_json_to_points(" [ ] ".as_bytes(), LineMode::Orthogonal).unwrap();
+        assert!(points.point_pairs.is_empty());
+    }
+
+    #[test]
+    fn test_json_string_escapes() {
+        let parse = |input: &str| JsonParser::new(input).parse_string();
+        assert_eq!(
+            parse(r#""a\rb\u00e9 \"\\\/\b\f\n\t""#).unwrap(),
+            "a\rb\u{e9} \"\\/\u{8}\u{c}\n\t"
+        );
+        assert_eq!(parse(r#""\ud83d\ude00""#).unwrap(), "\u{1f600}");
+        assert_eq!(
+            parse(r#""\ud83d x""#).unwrap_err().to_string(),
+            "line 1, column 9: unpaired surrogate in string"
+        );
+        assert!(parse(r#""\ude00""#).is_err());
+        assert!(parse(r#""\u00g0""#).is_err());
+        assert!(parse(r#""\x""#).is_err());
+    }
+
+    #[test]
+    fn test_read_json_to_points_errors() {
+        let input = "[\n  {\"x1\": 0, \"y1\": 9, \"x2\": 5}\n]";
+        let err = read_json_to_points(input.as_bytes(), LineMode::Orthogonal).unwrap_err();
+        assert_eq!(
+            parse_error(err).to_string(),
+            "line 2, column 3: missing \"y2\""
+        );
+        let input = "[{\"x1\": 0, \"y1\": 1.5, \"x2\": 5, \"y2\": 9}]";
+        let err = read_json_to_points(input.as_bytes(), LineMode::Orthogonal).unwrap_err();
+        assert_eq!(
+            parse_error(err).kind,
+            ParseErrorKind::InvalidNumber(String::from("1.5"))
+        );
+        let input = r#"[{"x1": 0, "y1": 1, "x2": 5, "y2": 9, "ok": yes}]"#;
+        let err = read_json_to_points(input.as_bytes(), LineMode::Orthogonal).unwrap_err();
+        assert_eq!(
+            parse_error(err).to_string(),
+            "line 1, column 45: unexpected 'yes'"
+        );
+        let input = r#"[{"x1": 0, "y1": null, "x2": 5, "y2": 9}]"#;
+        let err = read_json_to_points(input.as_bytes(), LineMode::Orthogonal).unwrap_err();
+        assert_eq!(
+            parse_error(err).to_string(),
+            "line 1, column 18: \"y1\" must be a number"
+        );
+        let input = r#"[{"x1": "0", "y1": 1, "x2": 5, "y2": 9}]"#;
+        let err = read_json_to_points(input.as_bytes(), LineMode::Orthogonal).unwrap_err();
+        assert_eq!(
+            parse_error(err).to_string(),
+            "line 1, column 9: \"x1\" must be a number, found \"0\""
+        );
+        let err = read_json_to_points("[{}".as_bytes(), LineMode::Orthogonal).unwrap_err();
+        assert_eq!(parse_error(err).line, 1);
+        let err = read_json_to_points("{}".as_bytes(), LineMode::Orthogonal).unwrap_err();
+        assert_eq!(
+            parse_error(err).to_string(),
+            "line 1, column 1: expected an array"
+        );
+    }
+
+    fn grid() -> Grid {
+        Grid::new(PointsData::new(
+            vec![pair(0, 0, 2, 0), pair(1, 0, 1, 1)],
+            LineMode::Orthogonal,
+        ))
+    }
+
+    #[test]
+    fn test_write_counts_csv() {
+        let mut output = Vec::new();
+        write_counts_csv(&grid(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "x,y,count\n0,0,1\n1,0,2\n2,0,1\n1,1,1\n"
+        );
+    }
+
+    #[test]
+    fn test_write_counts_json() {
+        let point_count = PointCount {
+            point: Coordinate { x: -1, y: 2 },
+            count: Count(3),
+        };
+        assert_eq!(point_count.to_json(), r#"{"x":-1,"y":2,"count":3}"#);
+
+        let mut output = Vec::new();
+        write_counts_json(&grid(), &mut output).unwrap();
+        let json = String::from_utf8(output).unwrap();
+        assert!(json.starts_with("[\n  {\"x\":0,\"y\":0,\"count\":1},\n"));
+        assert!(json.ends_with("  {\"x\":1,\"y\":1,\"count\":1}\n]\n"));
+    }
+}
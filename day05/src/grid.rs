@@ -6,69 +6,429 @@
 /// The print output from the Display trait for Grid struct is
 /// the visual Solution to part 1.
 use std::fmt;
+use std::str::FromStr;
+use std::thread;
 
-use crate::common::{Coordinate, Count, PointCount};
+use crate::common::{Coordinate, Count, PointCount, Rect};
+use crate::error::{ParseError, ParseErrorKind};
 use crate::file::PointsData;
-use crate::line::LineIterator;
+use crate::line::{LineMode, Rasterizer};
+use crate::render::cell_char;
+use crate::storage::{Backend, CellStorage};
 
 pub struct Grid {
-    cells: Vec<Vec<usize>>,
+    storage: Box<dyn CellStorage>,
+    backend: Backend,
+    /// Set when the backend was given to the builder, growing keeps it.
+    fixed_backend: bool,
+    rasterizer: Rasterizer,
+    bounds: Rect,
+    /// The cells the storage holds, at least the bounds. add_segment grows
+    /// it ahead of the bounds so the cells aren't copied on every line.
+    capacity: Rect,
+    points: PointsData,
+    /// Number of cells with a count of 2 or more, kept up to date on every change.
+    double_counts: usize,
 }
 
-impl Grid {
+/// Builds a Grid from the points, with the storage backend and the
+/// rasterizer used to draw the lines.
+pub struct GridBuilder {
+    points: PointsData,
+    backend: Option<Backend>,
+    rasterizer: Option<Rasterizer>,
+    threads: usize,
+}
+
+/// Converts a point inside the bounds to its (row, col) in the storage.
+fn to_cell(bounds: &Rect, point: &Coordinate) -> (usize, usize) {
+    (
+        point.y.abs_diff(bounds.min.y) as usize,
+        point.x.abs_diff(bounds.min.x) as usize,
+    )
+}
+
+/// Returns the capacity grown to cover the bounds, at least doubled along
+/// each side that has to grow, so adding lines one at a time only copies
+/// the cells a logarithmic number of times.
+fn grow_capacity(capacity: &Rect, bounds: &Rect) -> Rect {
+    let width = i64::try_from(capacity.width()).unwrap_or(i64::MAX);
+    let height = i64::try_from(capacity.height()).unwrap_or(i64::MAX);
+    let mut grown = capacity.union(bounds);
+    if bounds.min.x < capacity.min.x {
+        grown.min.x = grown.min.x.min(capacity.min.x.saturating_sub(width));
+    }
+    if bounds.max.x > capacity.max.x {
+        grown.max.x = grown.max.x.max(capacity.max.x.saturating_add(width));
+    }
+    if bounds.min.y < capacity.min.y {
+        grown.min.y = grown.min.y.min(capacity.min.y.saturating_sub(height));
+    }
+    if bounds.max.y > capacity.max.y {
+        grown.max.y = grown.max.y.max(capacity.max.y.saturating_add(height));
+    }
+    grown
+}
+
+/// Draws the lines into one storage per thread and merges them in order.
+fn draw_parallel(
+    lines: &[(Coordinate, Coordinate)],
+    bounds: Rect,
+    backend: Backend,
+    rasterizer: Rasterizer,
+    threads: usize,
+) -> Box<dyn CellStorage> {
+    let chunk_size = lines.len().div_ceil(threads).max(1);
+    let mut partials = thread::scope(|scope| {
+        let handles: Vec<_> = lines
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut storage = backend.storage(bounds.width(), bounds.height());
+                    for &(start, end) in chunk {
+                        for point in rasterizer.points(start, end) {
+                            let (row, col) = to_cell(&bounds, &point);
+                            storage.increment(row, col);
+                        }
+                    }
+                    storage
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("grid thread panicked"))
+            .collect::<Vec<_>>()
+    })
+    .into_iter();
+    let mut storage = partials
+        .next()
+        .unwrap_or_else(|| backend.storage(bounds.width(), bounds.height()));
+    for partial in partials {
+        for (row, col, count) in partial.iter_nonzero() {
+            storage.add(row, col, count);
+        }
+    }
+    storage
+}
+
+impl GridBuilder {
     pub fn new(points: PointsData) -> Self {
-        let mut grid = Self {
-            cells: vec![vec![0usize; points.max_x + 1]; points.max_y + 1],
-        };
-        points.iter().for_each(|coordinate| {
-            grid.add_line(coordinate.0, coordinate.1);
+        Self {
+            points,
+            backend: None,
+            rasterizer: None,
+            threads: 1,
+        }
+    }
+
+    /// Starts a Grid without lines, to add lines to one at a time.
+    pub fn empty(line_mode: LineMode) -> Self {
+        Self::new(PointsData::new(Vec::new(), line_mode))
+    }
+
+    /// Uses the given storage backend instead of picking one from
+    /// how densely the lines fill the bounding box, also when the grid
+    /// grows. A dense storage that doesn't fit, see Backend::fits, is
+    /// picked the usual way instead.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Uses the given rasterizer instead of the one for the line mode,
+    /// see Rasterizer::for_mode.
+    pub fn rasterizer(mut self, rasterizer: Rasterizer) -> Self {
+        self.rasterizer = Some(rasterizer);
+        self
+    }
+
+    /// Draws the lines on this many threads, each into its own partial
+    /// grid, merged in order at the end. The result does not depend on
+    /// the number of threads.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn build(self) -> Grid {
+        let mut points = self.points;
+        let rasterizer = self
+            .rasterizer
+            .unwrap_or(Rasterizer::for_mode(points.line_mode));
+        let lines: Vec<_> = points
+            .iter()
+            .filter(|(start, end)| points.line_mode.allows(start, end))
+            .copied()
+            .collect();
+        // The fields of the points are public, don't trust them to cover the lines.
+        let bounds = lines
+            .iter()
+            .map(|&(start, end)| Rect::new(start, end))
+            .fold(points.bounds(), |bounds, line| bounds.union(&line));
+        points.min_x = bounds.min.x;
+        points.min_y = bounds.min.y;
+        points.max_x = bounds.max.x;
+        points.max_y = bounds.max.y;
+        let fixed_backend = self
+            .backend
+            .filter(|backend| backend.fits(bounds.width(), bounds.height()));
+        let backend = fixed_backend.unwrap_or_else(|| {
+            let drawn_points = lines
+                .iter()
+                .map(|(start, end)| {
+                    (start.x.abs_diff(end.x).max(start.y.abs_diff(end.y)) as usize)
+                        .saturating_add(1)
+                })
+                .fold(0usize, usize::saturating_add);
+            Backend::choose(bounds.width(), bounds.height(), drawn_points)
         });
+        let mut grid = Grid {
+            storage: backend.storage(bounds.width(), bounds.height()),
+            backend,
+            fixed_backend: fixed_backend.is_some(),
+            rasterizer,
+            bounds,
+            capacity: bounds,
+            points,
+            double_counts: 0,
+        };
+        if self.threads > 1 {
+            grid.storage = draw_parallel(&lines, bounds, backend, rasterizer, self.threads);
+            grid.double_counts = grid.count_at_least(2);
+        } else {
+            lines.iter().for_each(|coordinate| {
+                grid.add_line(coordinate.0, coordinate.1);
+            });
+        }
         grid
     }
+}
+
+impl Grid {
+    /// Creates a Grid from the points, picking the storage backend
+    /// from how densely the lines fill the bounding box.
+    pub fn new(points: PointsData) -> Self {
+        GridBuilder::new(points).build()
+    }
+
+    /// Creates a Grid from the points using the given storage backend.
+    pub fn with_backend(points: PointsData, backend: Backend) -> Self {
+        GridBuilder::new(points).backend(backend).build()
+    }
+
+    pub fn builder(points: PointsData) -> GridBuilder {
+        GridBuilder::new(points)
+    }
+
+    /// Creates a Grid without lines, to add lines to one at a time.
+    /// Use GridBuilder::empty to pick the backend or the rasterizer.
+    pub fn empty(line_mode: LineMode) -> Self {
+        GridBuilder::empty(line_mode).build()
+    }
+
+    /// Adds the line from start to end, growing the grid when the line
+    /// falls outside its bounds. Its index follows the one of the last
+    /// line. Returns false, without adding it, when the line is not
+    /// allowed by the line mode.
+    pub fn add_segment(&mut self, start: Coordinate, end: Coordinate) -> bool {
+        if !self.points.line_mode.allows(&start, &end) {
+            return false;
+        }
+        self.grow_to(&Rect::new(start, end));
+        let index = self.points.indices.last().map_or(0, |index| index + 1);
+        self.points.point_pairs.push((start, end));
+        self.points.indices.push(index);
+        self.add_line(start, end);
+        true
+    }
+
+    /// Removes the first line from start to end. Returns false when
+    /// there is no such line. The grid never shrinks, but once its counts
+    /// are all gone the next line added moves it to that line.
+    pub fn remove_segment(&mut self, start: Coordinate, end: Coordinate) -> bool {
+        let Some(index) = self
+            .points
+            .point_pairs
+            .iter()
+            .position(|&pair| pair == (start, end))
+        else {
+            return false;
+        };
+        self.points.point_pairs.remove(index);
+        self.points.indices.remove(index);
+        if self.points.line_mode.allows(&start, &end) {
+            self.remove_line(start, end);
+        }
+        true
+    }
+
+    /// Makes the grid cover the rect. When the storage is too small the
+    /// counts are moved to a new storage, with room to grow further, and
+    /// the backend is picked again for the new size unless it was given
+    /// to the builder. A grid without counts is moved to the rect instead.
+    fn grow_to(&mut self, rect: &Rect) {
+        if self.bounds.contains_rect(rect) {
+            return;
+        }
+        let is_empty = self.storage.iter_nonzero().next().is_none();
+        let bounds = if is_empty {
+            *rect
+        } else {
+            self.bounds.union(rect)
+        };
+        if is_empty || !self.capacity.contains_rect(&bounds) {
+            let capacity = if is_empty {
+                bounds
+            } else {
+                grow_capacity(&self.capacity, &bounds)
+            };
+            let cells: Vec<_> = self.point_counts().collect();
+            let backend =
+                if self.fixed_backend && self.backend.fits(capacity.width(), capacity.height()) {
+                    self.backend
+                } else {
+                    Backend::choose(
+                        capacity.width(),
+                        capacity.height(),
+                        cells.len() + rect.width().max(rect.height()),
+                    )
+                };
+            let mut storage = backend.storage(capacity.width(), capacity.height());
+            for point_count in cells {
+                let (row, col) = to_cell(&capacity, &point_count.point);
+                storage.add(row, col, point_count.count.0);
+            }
+            self.storage = storage;
+            self.backend = backend;
+            self.capacity = capacity;
+        }
+        self.bounds = bounds;
+        self.points.min_x = bounds.min.x;
+        self.points.min_y = bounds.min.y;
+        self.points.max_x = bounds.max.x;
+        self.points.max_y = bounds.max.y;
+    }
+
+    /// Returns the points the grid was built from.
+    pub fn points(&self) -> &PointsData {
+        &self.points
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn rasterizer(&self) -> Rasterizer {
+        self.rasterizer
+    }
+
+    /// Returns the cells covered by the grid, from the top left
+    /// origin to the bottom right corner.
+    pub fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    pub fn width(&self) -> usize {
+        self.bounds.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.bounds.height()
+    }
+
+    /// Converts a (row, col) in the storage back to its point.
+    fn to_point(&self, row: usize, col: usize) -> Coordinate {
+        Coordinate {
+            x: self.capacity.min.x + col as i64,
+            y: self.capacity.min.y + row as i64,
+        }
+    }
+
+    /// Returns the highest count in the grid, 0 if the grid is empty.
+    pub fn max_count(&self) -> usize {
+        self.storage
+            .iter_nonzero()
+            .map(|(_, _, count)| count)
+            .max()
+            .unwrap_or(0)
+    }
 
     fn add_point(&mut self, point: Coordinate) {
-        self.cells[point.y][point.x] += 1;
+        let (row, col) = to_cell(&self.capacity, &point);
+        if self.storage.increment(row, col) == 2 {
+            self.double_counts += 1;
+        }
     }
 
     fn add_line(&mut self, start: Coordinate, end: Coordinate) {
-        LineIterator::new(start, end)
-            .collect::<Vec<Coordinate>>()
-            .iter()
-            .for_each(|point| {
-                self.add_point(*point);
-            });
+        self.rasterizer.points(start, end).for_each(|point| {
+            self.add_point(point);
+        });
+    }
+
+    fn remove_point(&mut self, point: Coordinate) {
+        let (row, col) = to_cell(&self.capacity, &point);
+        if self.storage.decrement(row, col) == 1 {
+            self.double_counts -= 1;
+        }
+    }
+
+    fn remove_line(&mut self, start: Coordinate, end: Coordinate) {
+        self.rasterizer.points(start, end).for_each(|point| {
+            self.remove_point(point);
+        });
     }
 
     pub fn get_count(&self, point: &Coordinate) -> Option<PointCount> {
-        if point.y < self.cells.len() && point.x < self.cells[point.y].len() {
+        if self.bounds.contains(point) {
+            let (row, col) = to_cell(&self.capacity, point);
             Some(PointCount {
                 point: *point,
-                count: Count(self.cells[point.y][point.x]),
+                count: Count(self.storage.get(row, col)),
             })
         } else {
             None
         }
     }
 
+    /// Iterates over every cell with a count above 0, in no particular order.
+    pub fn point_counts(&self) -> impl Iterator<Item = PointCount> + '_ {
+        self.storage
+            .iter_nonzero()
+            .map(|(row, col, count)| PointCount {
+                point: self.to_point(row, col),
+                count: Count(count),
+            })
+    }
+
     pub fn sum_double_counts(&self) -> i32 {
-        self.cells
-            .iter()
-            .flatten()
-            .filter(|&&count| count >= 2)
-            .count() as i32
+        self.double_counts as i32
     }
 }
 
+/// The text before the top left cell of a printed grid that doesn't start at 0,0.
+const ORIGIN_HEADER: &str = "origin ";
+
+/// The grid is printed from its origin in the top left corner to the
+/// bottom right corner of its bounds, so it prints width x height cells.
+/// Grids that don't start at 0,0 start with a line "origin x,y" so they
+/// read back in place. Each cell is one character, as in render.rs: '.'
+/// for 0, the digit for 1 to 9 and '#' for anything above 9.
 impl fmt::Display for Grid {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         // writeln!(f, "Grid:")?;
-        for row in &self.cells {
-            for &cell in row {
-                if cell == 0 {
-                    write!(f, ".")?;
-                } else {
-                    write!(f, "{}", cell)?;
-                }
+        let origin = self.bounds.min;
+        if origin != (Coordinate { x: 0, y: 0 }) {
+            writeln!(f, "{}{},{}", ORIGIN_HEADER, origin.x, origin.y)?;
+        }
+        for y in origin.y..=self.bounds.max.y {
+            for x in origin.x..=self.bounds.max.x {
+                let cell = self
+                    .get_count(&Coordinate { x, y })
+                    .map(|point_count| point_count.count.0)
+                    .unwrap_or(0);
+                write!(f, "{}", cell_char(cell))?;
             }
             writeln!(f)?;
         }
@@ -76,6 +436,111 @@ impl fmt::Display for Grid {
     }
 }
 
+/// Reads a grid back from its Display format: one row per line, with '.'
+/// for an empty cell and a digit for a count. Trailing blank lines are
+/// ignored so the output of println! reads back too. The grid starts at
+/// the origin given on an optional first line "origin x,y", or at 0,0,
+/// and only holds counts, without lines. Counts above 9 print as '#' and
+/// are refused with CountTooLarge, they can't be read back.
+impl FromStr for Grid {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .trim_end_matches(['\n', '\r'])
+            .lines()
+            .enumerate()
+            .peekable();
+        let mut origin = Coordinate { x: 0, y: 0 };
+        if let Some(header) = lines
+            .peek()
+            .and_then(|(_, line)| line.strip_prefix(ORIGIN_HEADER))
+        {
+            origin = header
+                .parse()
+                .map_err(|err: ParseError| err.relocate(1, ORIGIN_HEADER.len()))?;
+            lines.next();
+        }
+        let mut rows = Vec::new();
+        for (index, line) in lines {
+            let cells = line
+                .chars()
+                .enumerate()
+                .map(|(column, cell)| match cell {
+                    '.' => Ok(0),
+                    '1'..='9' => Ok(cell as usize - '0' as usize),
+                    '#' => Err(ParseError::new(
+                        index + 1,
+                        column + 1,
+                        ParseErrorKind::CountTooLarge,
+                    )),
+                    _ => Err(ParseError::new(
+                        index + 1,
+                        column + 1,
+                        ParseErrorKind::InvalidCell(cell),
+                    )),
+                })
+                .collect::<Result<Vec<usize>, _>>()?;
+            if cells.is_empty() {
+                return Err(ParseError::new(index + 1, 1, ParseErrorKind::EmptyLine));
+            }
+            if let Some(first) = rows.first().map(Vec::len) {
+                if cells.len() != first {
+                    return Err(ParseError::new(
+                        index + 1,
+                        cells.len().min(first) + 1,
+                        ParseErrorKind::RaggedRow {
+                            expected: first,
+                            found: cells.len(),
+                        },
+                    ));
+                }
+            }
+            rows.push(cells);
+        }
+        let Some(width) = rows.first().map(Vec::len) else {
+            return Ok(Grid::empty(LineMode::default()));
+        };
+
+        let bounds = Rect::new(
+            origin,
+            Coordinate {
+                x: origin.x + width as i64 - 1,
+                y: origin.y + rows.len() as i64 - 1,
+            },
+        );
+        let filled = rows.iter().flatten().filter(|&&count| count > 0).count();
+        let backend = Backend::choose(bounds.width(), bounds.height(), filled);
+        let mut storage = backend.storage(bounds.width(), bounds.height());
+        let mut double_counts = 0;
+        for (row, cells) in rows.iter().enumerate() {
+            for (col, &count) in cells.iter().enumerate() {
+                if count > 0 {
+                    storage.add(row, col, count);
+                }
+                if count >= 2 {
+                    double_counts += 1;
+                }
+            }
+        }
+        let mut points = PointsData::new(Vec::new(), LineMode::default());
+        points.min_x = bounds.min.x;
+        points.min_y = bounds.min.y;
+        points.max_x = bounds.max.x;
+        points.max_y = bounds.max.y;
+        Ok(Grid {
+            storage,
+            backend,
+            fixed_backend: false,
+            rasterizer: Rasterizer::default(),
+            bounds,
+            capacity: bounds,
+            points,
+            double_counts,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -88,12 +553,16 @@ mod tests {
                 (Coordinate { x: 0, y: 0 }, Coordinate { x: 0, y: 1 }),
                 (Coordinate { x: 0, y: 1 }, Coordinate { x: 1, y: 1 }),
             ],
+            indices: vec![0, 1],
+            min_x: 0,
+            min_y: 0,
             max_x: 1,
             max_y: 1,
+            line_mode: LineMode::Orthogonal,
         };
         let grid = Grid::new(points_data);
-        assert_eq!(grid.cells.len(), 2);
-        assert_eq!(grid.cells[0].len(), 2);
+        assert_eq!(grid.height(), 2);
+        assert_eq!(grid.width(), 2);
     }
 
     #[test]
@@ -103,8 +572,12 @@ mod tests {
                 (Coordinate { x: 0, y: 0 }, Coordinate { x: 0, y: 1 }),
                 (Coordinate { x: 2, y: 2 }, Coordinate { x: 3, y: 3 }),
             ],
+            indices: vec![0, 1],
+            min_x: 0,
+            min_y: 0,
             max_x: 1,
             max_y: 1,
+            line_mode: LineMode::Orthogonal,
         };
         let grid = Grid::new(points_data);
         let count_origin = grid.get_count(&Coordinate { x: 0, y: 0 });
@@ -116,4 +589,448 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn test_grid_diagonal_mode() {
+        let points_data = PointsData {
+            point_pairs: vec![
+                (Coordinate { x: 0, y: 0 }, Coordinate { x: 2, y: 2 }),
+                (Coordinate { x: 2, y: 0 }, Coordinate { x: 0, y: 2 }),
+            ],
+            indices: vec![0, 1],
+            min_x: 0,
+            min_y: 0,
+            max_x: 2,
+            max_y: 2,
+            line_mode: LineMode::Diagonal,
+        };
+        let grid = Grid::new(points_data);
+        assert_eq!(grid.sum_double_counts(), 1);
+        assert_eq!(
+            grid.get_count(&Coordinate { x: 1, y: 1 }).unwrap().count,
+            Count(2)
+        );
+    }
+
+    #[test]
+    fn test_grid_sparse_backend() {
+        let points_data = PointsData {
+            point_pairs: vec![
+                (Coordinate { x: 0, y: 0 }, Coordinate { x: 0, y: 1 }),
+                (Coordinate { x: 0, y: 1 }, Coordinate { x: 1, y: 1 }),
+                (
+                    Coordinate {
+                        x: 999_999,
+                        y: 999_999,
+                    },
+                    Coordinate {
+                        x: 999_999,
+                        y: 999_998,
+                    },
+                ),
+            ],
+            indices: vec![0, 1, 2],
+            min_x: 0,
+            min_y: 0,
+            max_x: 999_999,
+            max_y: 999_999,
+            line_mode: LineMode::Orthogonal,
+        };
+        let grid = Grid::new(points_data);
+        assert_eq!(grid.backend(), Backend::Sparse);
+        assert_eq!(grid.sum_double_counts(), 1);
+        assert_eq!(
+            grid.get_count(&Coordinate {
+                x: 999_999,
+                y: 999_998
+            }),
+            Some(PointCount {
+                point: Coordinate {
+                    x: 999_999,
+                    y: 999_998
+                },
+                count: Count(1)
+            })
+        );
+    }
+
+    #[test]
+    fn test_grid_display_same_for_backends() {
+        let make_points = || PointsData {
+            point_pairs: vec![
+                (Coordinate { x: 0, y: 0 }, Coordinate { x: 2, y: 0 }),
+                (Coordinate { x: 1, y: 0 }, Coordinate { x: 1, y: 1 }),
+            ],
+            indices: vec![0, 1],
+            min_x: 0,
+            min_y: 0,
+            max_x: 2,
+            max_y: 1,
+            line_mode: LineMode::Orthogonal,
+        };
+        let dense = Grid::with_backend(make_points(), Backend::Dense);
+        let sparse = Grid::with_backend(make_points(), Backend::Sparse);
+        assert_eq!(dense.to_string(), "121\n.1.\n");
+        assert_eq!(dense.to_string(), sparse.to_string());
+        assert_eq!(dense.sum_double_counts(), sparse.sum_double_counts());
+    }
+
+    #[test]
+    fn test_grid_offset_origin() {
+        let points_data = PointsData::new(
+            vec![
+                (Coordinate { x: -2, y: 5 }, Coordinate { x: 0, y: 5 }),
+                (Coordinate { x: -1, y: 4 }, Coordinate { x: -1, y: 6 }),
+            ],
+            LineMode::Orthogonal,
+        );
+        let grid = Grid::new(points_data);
+        assert_eq!(grid.width(), 3);
+        assert_eq!(grid.height(), 3);
+        assert_eq!(grid.sum_double_counts(), 1);
+        assert_eq!(
+            grid.get_count(&Coordinate { x: -1, y: 5 }).unwrap().count,
+            Count(2)
+        );
+        assert_eq!(grid.get_count(&Coordinate { x: 0, y: 0 }), None);
+        assert_eq!(grid.to_string(), "origin -2,4\n.1.\n121\n.1.\n");
+    }
+
+    #[test]
+    fn test_grid_display_starts_at_origin() {
+        let points_data = PointsData::new(
+            vec![(Coordinate { x: 2, y: 1 }, Coordinate { x: 3, y: 1 })],
+            LineMode::Orthogonal,
+        );
+        let grid = Grid::new(points_data);
+        assert_eq!(grid.width(), 2);
+        assert_eq!(grid.height(), 1);
+        let printed = grid.to_string();
+        assert_eq!(printed, "origin 2,1\n11\n");
+        let rows: Vec<_> = printed.lines().skip(1).collect();
+        assert!(rows.iter().all(|row| row.len() == grid.width()));
+        assert_eq!(rows.len(), grid.height());
+
+        // A small field far from 0,0 prints small.
+        let far = Grid::new(PointsData::new(
+            vec![(
+                Coordinate {
+                    x: 1_000_000,
+                    y: 1_000_000,
+                },
+                Coordinate {
+                    x: 1_000_002,
+                    y: 1_000_000,
+                },
+            )],
+            LineMode::Orthogonal,
+        ));
+        assert_eq!(far.to_string(), "origin 1000000,1000000\n111\n");
+    }
+
+    #[test]
+    fn test_grid_builder_bresenham() {
+        let make_points = || {
+            PointsData::new(
+                vec![
+                    (Coordinate { x: 0, y: 0 }, Coordinate { x: 4, y: 2 }),
+                    (Coordinate { x: 0, y: 1 }, Coordinate { x: 4, y: 1 }),
+                ],
+                LineMode::Any,
+            )
+        };
+        let grid = Grid::builder(make_points())
+            .rasterizer(Rasterizer::Bresenham)
+            .build();
+        assert_eq!(grid.rasterizer(), Rasterizer::Bresenham);
+        assert_eq!(grid.sum_double_counts(), 2);
+        assert_eq!(grid.to_string(), "1....\n12211\n...11\n");
+
+        let grid = Grid::builder(make_points())
+            .backend(Backend::Sparse)
+            .rasterizer(Rasterizer::Bresenham)
+            .build();
+        assert_eq!(grid.backend(), Backend::Sparse);
+        assert_eq!(grid.sum_double_counts(), 2);
+    }
+
+    #[test]
+    fn test_grid_builder_threads() {
+        let make_points = || {
+            PointsData::new(
+                (0..40)
+                    .map(|i| {
+                        (
+                            Coordinate { x: i % 7, y: 0 },
+                            Coordinate {
+                                x: i % 7,
+                                y: 10 + i % 5,
+                            },
+                        )
+                    })
+                    .chain((0..20).map(|i| (Coordinate { x: 0, y: i }, Coordinate { x: 9, y: i })))
+                    .collect(),
+                LineMode::Orthogonal,
+            )
+        };
+        let single = Grid::new(make_points());
+        for backend in [Backend::Dense, Backend::Sparse] {
+            for threads in [2, 3, 8, 100] {
+                let grid = Grid::builder(make_points())
+                    .backend(backend)
+                    .threads(threads)
+                    .build();
+                assert_eq!(grid.to_string(), single.to_string());
+                assert_eq!(grid.sum_double_counts(), single.sum_double_counts());
+            }
+        }
+    }
+
+    #[test]
+    fn test_grid_add_and_remove_segment() {
+        let mut grid = Grid::empty(LineMode::Orthogonal);
+        assert_eq!(grid.sum_double_counts(), 0);
+
+        assert!(grid.add_segment(Coordinate { x: 5, y: 5 }, Coordinate { x: 7, y: 5 }));
+        assert_eq!(
+            grid.bounds(),
+            Rect::new(Coordinate { x: 5, y: 5 }, Coordinate { x: 7, y: 5 })
+        );
+
+        // Grows to the left and down.
+        assert!(grid.add_segment(Coordinate { x: 6, y: 8 }, Coordinate { x: 6, y: -2 }));
+        assert_eq!(
+            grid.bounds(),
+            Rect::new(Coordinate { x: 5, y: -2 }, Coordinate { x: 7, y: 8 })
+        );
+        assert_eq!(grid.sum_double_counts(), 1);
+        assert!(grid.add_segment(Coordinate { x: 5, y: 5 }, Coordinate { x: 6, y: 5 }));
+        assert_eq!(grid.sum_double_counts(), 2);
+        assert!(!grid.add_segment(Coordinate { x: 0, y: 0 }, Coordinate { x: 1, y: 1 }));
+        assert_eq!(grid.points().point_pairs.len(), 3);
+
+        assert!(grid.remove_segment(Coordinate { x: 6, y: 8 }, Coordinate { x: 6, y: -2 }));
+        assert_eq!(grid.sum_double_counts(), 2);
+        assert!(grid.remove_segment(Coordinate { x: 5, y: 5 }, Coordinate { x: 7, y: 5 }));
+        assert_eq!(grid.sum_double_counts(), 0);
+        assert!(!grid.remove_segment(Coordinate { x: 5, y: 5 }, Coordinate { x: 7, y: 5 }));
+        assert_eq!(
+            grid.get_count(&Coordinate { x: 6, y: 5 }).unwrap().count,
+            Count(1)
+        );
+        assert_eq!(grid.count_at_least(1), 2);
+
+        // Without counts left the grid moves to the next line.
+        assert!(grid.remove_segment(Coordinate { x: 5, y: 5 }, Coordinate { x: 6, y: 5 }));
+        assert_eq!(grid.max_count(), 0);
+        assert!(grid.add_segment(Coordinate { x: 20, y: 30 }, Coordinate { x: 20, y: 31 }));
+        assert_eq!(
+            grid.bounds(),
+            Rect::new(Coordinate { x: 20, y: 30 }, Coordinate { x: 20, y: 31 })
+        );
+        assert_eq!(grid.to_string(), "origin 20,30\n1\n1\n");
+    }
+
+    #[test]
+    fn test_grid_bounds_cover_lines() {
+        // The bounds of the points leave out the second line.
+        let make_points = || PointsData {
+            point_pairs: vec![
+                (Coordinate { x: 0, y: 0 }, Coordinate { x: 1, y: 0 }),
+                (Coordinate { x: 1, y: 0 }, Coordinate { x: 1, y: 3 }),
+            ],
+            indices: vec![0, 1],
+            min_x: 0,
+            min_y: 0,
+            max_x: 1,
+            max_y: 1,
+            line_mode: LineMode::Orthogonal,
+        };
+        for backend in [Backend::Dense, Backend::Sparse] {
+            let grid = Grid::with_backend(make_points(), backend);
+            assert_eq!(
+                grid.bounds(),
+                Rect::new(Coordinate { x: 0, y: 0 }, Coordinate { x: 1, y: 3 })
+            );
+            assert_eq!(grid.points().bounds(), grid.bounds());
+            assert_eq!(grid.to_string(), "12\n.1\n.1\n.1\n");
+        }
+    }
+
+    #[test]
+    fn test_grid_fixed_dense_backend_grows_sparse() {
+        let mut grid = GridBuilder::empty(LineMode::Orthogonal)
+            .backend(Backend::Dense)
+            .build();
+        grid.add_segment(Coordinate { x: 0, y: 0 }, Coordinate { x: 1, y: 0 });
+        assert_eq!(grid.backend(), Backend::Dense);
+        let far = Coordinate {
+            x: 1 << 20,
+            y: 1 << 20,
+        };
+        grid.add_segment(far, far);
+        assert_eq!(grid.backend(), Backend::Sparse);
+        assert_eq!(grid.count_at_least(1), 3);
+        assert_eq!(grid.get_count(&far).unwrap().count, Count(1));
+    }
+
+    #[test]
+    fn test_grid_incremental_matches_new() {
+        let pairs = vec![
+            (Coordinate { x: 0, y: 9 }, Coordinate { x: 5, y: 9 }),
+            (Coordinate { x: 9, y: 4 }, Coordinate { x: 3, y: 4 }),
+            (Coordinate { x: 2, y: 2 }, Coordinate { x: 2, y: 1 }),
+            (Coordinate { x: 7, y: 0 }, Coordinate { x: 7, y: 4 }),
+            (Coordinate { x: 0, y: 9 }, Coordinate { x: 2, y: 9 }),
+            (Coordinate { x: 3, y: 4 }, Coordinate { x: 1, y: 4 }),
+        ];
+        let mut grid = Grid::empty(LineMode::Orthogonal);
+        for &(start, end) in &pairs {
+            grid.add_segment(start, end);
+        }
+        let built = Grid::new(PointsData::new(pairs, LineMode::Orthogonal));
+        assert_eq!(grid.sum_double_counts(), 5);
+        assert_eq!(grid.bounds(), built.bounds());
+        assert_eq!(grid.to_string(), built.to_string());
+    }
+
+    #[test]
+    fn test_grid_builder_empty_grows_geometrically() {
+        let mut grid = GridBuilder::empty(LineMode::Any)
+            .backend(Backend::Sparse)
+            .rasterizer(Rasterizer::Stepped)
+            .build();
+        let mut pairs = Vec::new();
+        let mut regrowths = 0;
+        for i in 0..1000 {
+            let (start, end) = (
+                Coordinate { x: i, y: -i },
+                Coordinate {
+                    x: i + 2,
+                    y: -i + 1,
+                },
+            );
+            let capacity = grid.capacity;
+            assert!(grid.add_segment(start, end));
+            pairs.push((start, end));
+            if grid.capacity != capacity {
+                regrowths += 1;
+            }
+        }
+        assert!(regrowths <= 30, "grew {} times", regrowths);
+        assert_eq!(grid.backend(), Backend::Sparse);
+        assert_eq!(grid.rasterizer(), Rasterizer::Stepped);
+        assert!(grid.capacity.contains_rect(&grid.bounds()));
+
+        let built = Grid::builder(PointsData::new(pairs, LineMode::Any))
+            .rasterizer(Rasterizer::Stepped)
+            .build();
+        assert_eq!(grid.bounds(), built.bounds());
+        assert_eq!(grid.sum_double_counts(), built.sum_double_counts());
+        let mut counts: Vec<_> = grid.point_counts().collect();
+        let mut built_counts: Vec<_> = built.point_counts().collect();
+        counts.sort_by_key(|point_count| (point_count.point.x, point_count.point.y));
+        built_counts.sort_by_key(|point_count| (point_count.point.x, point_count.point.y));
+        assert_eq!(counts, built_counts);
+    }
+
+    #[test]
+    fn test_grid_from_str_round_trip() {
+        let text = "1.1....11.\n.111...2..\n..2.1.111.\n...1.2.2..\n.112313211\n\
+                    ...1.2....\n..1...1...\n.1.....1..\n1.......1.\n222111....\n";
+        let grid: Grid = text.parse().unwrap();
+        assert_eq!(grid.width(), 10);
+        assert_eq!(grid.height(), 10);
+        assert_eq!(grid.sum_double_counts(), 12);
+        assert_eq!(grid.max_count(), 3);
+        assert_eq!(grid.to_string(), text);
+        let printed: Grid = format!("{}\n", grid).parse().unwrap();
+        assert_eq!(printed.to_string(), text);
+    }
+
+    #[test]
+    fn test_grid_from_str_round_trip_origin() {
+        let grid = Grid::new(PointsData::new(
+            vec![
+                (Coordinate { x: -3, y: -1 }, Coordinate { x: 1, y: -1 }),
+                (Coordinate { x: -1, y: -2 }, Coordinate { x: -1, y: 2 }),
+                (Coordinate { x: 2, y: 3 }, Coordinate { x: 0, y: 3 }),
+            ],
+            LineMode::Orthogonal,
+        ));
+        let text = "origin -3,-2\n\
+                    ..1...\n11211.\n..1...\n..1...\n..1...\n...111\n";
+        assert_eq!(grid.to_string(), text);
+        let printed: Grid = format!("{}\n", grid).parse().unwrap();
+        assert_eq!(printed.bounds(), grid.bounds());
+        assert_eq!(printed.to_string(), text);
+        assert_eq!(
+            printed
+                .get_count(&Coordinate { x: -1, y: -1 })
+                .unwrap()
+                .count,
+            Count(2)
+        );
+
+        // Only one coordinate is negative, the other starts at 0.
+        let grid: Grid = "origin 0,-1\n.1\n1.\n".parse().unwrap();
+        assert_eq!(
+            grid.get_count(&Coordinate { x: 0, y: 0 }).unwrap().count,
+            Count(1)
+        );
+        assert_eq!(grid.to_string(), "origin 0,-1\n.1\n1.\n");
+        let grid: Grid = "origin 2,3\n1\n".parse().unwrap();
+        assert_eq!(grid.to_string(), "origin 2,3\n1\n");
+        let printed: Grid = grid.to_string().parse().unwrap();
+        assert_eq!(printed.bounds(), grid.bounds());
+    }
+
+    #[test]
+    fn test_grid_from_str_count_too_large() {
+        let pairs = vec![(Coordinate { x: 0, y: 0 }, Coordinate { x: 1, y: 0 }); 12];
+        let grid = Grid::new(PointsData::new(pairs, LineMode::Orthogonal));
+        assert_eq!(grid.max_count(), 12);
+        assert_eq!(grid.to_string(), "##\n");
+        assert_eq!(
+            grid.to_string().parse::<Grid>().err(),
+            Some(ParseError::new(1, 1, ParseErrorKind::CountTooLarge))
+        );
+    }
+
+    #[test]
+    fn test_grid_from_str_add_segment() {
+        let mut grid: Grid = "..\n.2\n".parse().unwrap();
+        assert!(grid.add_segment(Coordinate { x: 1, y: 1 }, Coordinate { x: 1, y: 3 }));
+        assert_eq!(grid.to_string(), "..\n.3\n.1\n.1\n");
+        assert_eq!(grid.sum_double_counts(), 1);
+    }
+
+    #[test]
+    fn test_grid_from_str_errors() {
+        assert_eq!(
+            "..1\n.2\n".parse::<Grid>().err(),
+            Some(ParseError::new(
+                2,
+                3,
+                ParseErrorKind::RaggedRow {
+                    expected: 3,
+                    found: 2
+                }
+            ))
+        );
+        assert_eq!(
+            "..1\n.x.\n".parse::<Grid>().err(),
+            Some(ParseError::new(2, 2, ParseErrorKind::InvalidCell('x')))
+        );
+        assert_eq!(
+            "..1\n\n...\n".parse::<Grid>().err(),
+            Some(ParseError::new(2, 1, ParseErrorKind::EmptyLine))
+        );
+        assert_eq!(
+            "origin 1;2\n..\n".parse::<Grid>().err(),
+            Some(ParseError::new(1, 8, ParseErrorKind::MissingComma))
+        );
+        let empty: Grid = "\n".parse().unwrap();
+        assert_eq!(empty.max_count(), 0);
+    }
 }
@@ -0,0 +1,212 @@
+/// This file writes a Grid as a binary PGM (grayscale) or PPM (color)
+/// image, one pixel per cell, so large vent fields can be inspected in
+/// an image viewer instead of as an ASCII wall.
+///
+/// Counts are mapped through a ColorRamp scaled to the highest count in
+/// the grid, and cells at or above the danger threshold (2 by default)
+/// are drawn in a highlight color.
+use std::io::{self, Write};
+
+use crate::common::{Coordinate, Rect};
+use crate::grid::Grid;
+
+pub type Rgb = [u8; 3];
+
+/// Colors evenly spaced from a count of 0 up to the highest count,
+/// colors in between are linearly interpolated.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ColorRamp {
+    stops: Vec<Rgb>,
+}
+
+impl ColorRamp {
+    /// Creates a ramp from the stops, which must not be empty.
+    pub fn new(stops: Vec<Rgb>) -> Self {
+        assert!(!stops.is_empty(), "a color ramp needs at least one stop");
+        Self { stops }
+    }
+
+    /// Black to white.
+    pub fn grayscale() -> Self {
+        Self::new(vec![[0, 0, 0], [255, 255, 255]])
+    }
+
+    /// Black through blue and yellow to white.
+    pub fn heat() -> Self {
+        Self::new(vec![
+            [0, 0, 0],
+            [40, 40, 160],
+            [230, 200, 40],
+            [255, 255, 255],
+        ])
+    }
+
+    /// Returns the color for count when the highest count is max_count.
+    pub fn color(&self, count: usize, max_count: usize) -> Rgb {
+        if count == 0 || max_count == 0 || self.stops.len() == 1 {
+            return self.stops[0];
+        }
+        let position = count.min(max_count) as f64 / max_count as f64;
+        let scaled = position * (self.stops.len() - 1) as f64;
+        let index = (scaled.floor() as usize).min(self.stops.len() - 2);
+        let fraction = scaled - index as f64;
+        let (from, to) = (self.stops[index], self.stops[index + 1]);
+        let mut color = [0; 3];
+        for channel in 0..3 {
+            let value =
+                from[channel] as f64 + (to[channel] as f64 - from[channel] as f64) * fraction;
+            color[channel] = value.round() as u8;
+        }
+        color
+    }
+}
+
+impl Default for ColorRamp {
+    fn default() -> Self {
+        Self::heat()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImageOptions {
+    pub ramp: ColorRamp,
+    /// Color for cells at or above the threshold, None to use the ramp.
+    pub highlight: Option<Rgb>,
+    pub threshold: usize,
+    /// Cells to draw, None to draw the whole grid.
+    pub region: Option<Rect>,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            ramp: ColorRamp::default(),
+            highlight: Some([255, 0, 0]),
+            threshold: 2,
+            region: None,
+        }
+    }
+}
+
+fn count_at(grid: &Grid, x: i64, y: i64) -> usize {
+    grid.get_count(&Coordinate { x, y })
+        .map(|point_count| point_count.count.0)
+        .unwrap_or(0)
+}
+
+/// Writes the grid as a binary PGM. Empty cells are black, cells below
+/// the threshold are scaled into dark gray and cells at or above the
+/// threshold are white. The ramp and highlight of the options are not used.
+pub fn write_pgm(grid: &Grid, options: &ImageOptions, mut writer: impl Write) -> io::Result<()> {
+    let threshold = options.threshold;
+    let max_below = threshold.saturating_sub(1).max(1);
+    let bounds = options.region.unwrap_or(grid.bounds());
+    write!(writer, "P5\n{} {}\n255\n", bounds.width(), bounds.height())?;
+    for y in bounds.min.y..=bounds.max.y {
+        let row: Vec<u8> = (bounds.min.x..=bounds.max.x)
+            .map(|x| match count_at(grid, x, y) {
+                0 => 0,
+                count if count >= threshold => 255,
+                count => (64 + count * 63 / max_below) as u8,
+            })
+            .collect();
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+/// Writes the grid as a binary PPM colored by the options.
+pub fn write_ppm(grid: &Grid, options: &ImageOptions, mut writer: impl Write) -> io::Result<()> {
+    let max_count = grid.max_count();
+    let bounds = options.region.unwrap_or(grid.bounds());
+    write!(writer, "P6\n{} {}\n255\n", bounds.width(), bounds.height())?;
+    for y in bounds.min.y..=bounds.max.y {
+        let row: Vec<u8> = (bounds.min.x..=bounds.max.x)
+            .flat_map(|x| {
+                let count = count_at(grid, x, y);
+                match options.highlight {
+                    Some(color) if count >= options.threshold => color,
+                    _ => options.ramp.color(count, max_count),
+                }
+            })
+            .collect();
+        writer.write_all(&row)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::PointsData;
+    use crate::line::LineMode;
+
+    fn grid() -> Grid {
+        Grid::new(PointsData {
+            point_pairs: vec![
+                (Coordinate { x: 0, y: 0 }, Coordinate { x: 2, y: 0 }),
+                (Coordinate { x: 1, y: 0 }, Coordinate { x: 1, y: 1 }),
+            ],
+            indices: vec![0, 1],
+            min_x: 0,
+            min_y: 0,
+            max_x: 2,
+            max_y: 1,
+            line_mode: LineMode::Orthogonal,
+        })
+    }
+
+    #[test]
+    fn test_color_ramp() {
+        let ramp = ColorRamp::grayscale();
+        assert_eq!(ramp.color(0, 4), [0, 0, 0]);
+        assert_eq!(ramp.color(2, 4), [128, 128, 128]);
+        assert_eq!(ramp.color(4, 4), [255, 255, 255]);
+        assert_eq!(ramp.color(9, 4), [255, 255, 255]);
+        assert_eq!(ColorRamp::new(vec![[1, 2, 3]]).color(5, 5), [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_write_pgm() {
+        let mut image = Vec::new();
+        write_pgm(&grid(), &ImageOptions::default(), &mut image).unwrap();
+        let header = b"P5\n3 2\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(&image[header.len()..], &[127, 255, 127, 0, 127, 0]);
+    }
+
+    #[test]
+    fn test_write_ppm_highlight() {
+        let options = ImageOptions {
+            ramp: ColorRamp::grayscale(),
+            highlight: Some([255, 0, 0]),
+            threshold: 2,
+            region: None,
+        };
+        let mut image = Vec::new();
+        write_ppm(&grid(), &options, &mut image).unwrap();
+        let header = b"P6\n3 2\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        let pixels = &image[header.len()..];
+        assert_eq!(pixels.len(), 3 * 2 * 3);
+        assert_eq!(&pixels[0..3], &[128, 128, 128]);
+        assert_eq!(&pixels[3..6], &[255, 0, 0]);
+        assert_eq!(&pixels[9..12], &[0, 0, 0]);
+    }
+
+    #[test]
+    fn test_write_pgm_region() {
+        let options = ImageOptions {
+            region: Some(Rect::new(
+                Coordinate { x: 1, y: 0 },
+                Coordinate { x: 3, y: 0 },
+            )),
+            ..ImageOptions::default()
+        };
+        let mut image = Vec::new();
+        write_pgm(&grid(), &options, &mut image).unwrap();
+        let header = b"P5\n3 1\n255\n";
+        assert_eq!(&image[..header.len()], header);
+        assert_eq!(&image[header.len()..], &[255, 127, 0]);
+    }
+}
@@ -0,0 +1,283 @@
+/// This file builds a spatial index over the vent lines, to find the
+/// lines through a point or touching a rect without checking every line.
+///
+/// The plane is cut into square buckets and every line is filed under
+/// each bucket it passes through, found one row of buckets at a time from
+/// the start and end of the line. Nothing is rasterized, so the index
+/// works for inputs far too large to draw into a Grid. A query only looks
+/// at the lines in the buckets it covers and checks each of those exactly.
+///
+/// Lines of any other slope are drawn with Bresenham's algorithm, which can
+/// stray half a cell from the true line, so they are filed a little wider
+/// and checked by rasterizing them, like in intersect.rs.
+use std::collections::HashMap;
+
+use crate::common::{Coordinate, Rect};
+use crate::file::PointsData;
+use crate::intersect::{segment_contains, segment_touches};
+use crate::line::{BresenhamIterator, Orientation};
+
+/// Buckets of lines over the points, by their indices into the point pairs.
+/// Only the lines allowed by the line mode are indexed.
+pub struct SegmentIndex<'a> {
+    points: &'a PointsData,
+    bucket_size: i64,
+    buckets: HashMap<(i64, i64), Vec<usize>>,
+}
+
+fn floor_div(numerator: i128, denominator: i128) -> i128 {
+    numerator.div_euclid(denominator)
+}
+
+fn ceil_div(numerator: i128, denominator: i128) -> i128 {
+    -(-numerator).div_euclid(denominator)
+}
+
+fn is_skewed(start: &Coordinate, end: &Coordinate) -> bool {
+    Orientation::of(start, end) == Orientation::Other
+}
+
+impl<'a> SegmentIndex<'a> {
+    /// Indexes the points with buckets sized so there are about as many
+    /// buckets over the bounds as there are lines.
+    pub fn new(points: &'a PointsData) -> Self {
+        let bounds = points.bounds();
+        let area = bounds.width() as f64 * bounds.height() as f64;
+        let lines = points.point_pairs.len().max(1) as f64;
+        let bucket_size = (area / lines).sqrt().ceil().clamp(1.0, i64::MAX as f64) as i64;
+        Self::with_bucket_size(points, bucket_size)
+    }
+
+    /// Indexes the points with square buckets of bucket_size cells a side.
+    pub fn with_bucket_size(points: &'a PointsData, bucket_size: i64) -> Self {
+        let mut index = Self {
+            points,
+            bucket_size: bucket_size.max(1),
+            buckets: HashMap::new(),
+        };
+        for (i, &(start, end)) in points.point_pairs.iter().enumerate() {
+            if points.line_mode.allows(&start, &end) {
+                index.insert(i, start, end);
+            }
+        }
+        index
+    }
+
+    pub fn bucket_size(&self) -> i64 {
+        self.bucket_size
+    }
+
+    fn bucket_of(&self, value: i64) -> i64 {
+        value.div_euclid(self.bucket_size)
+    }
+
+    /// Files the line under every bucket it passes through.
+    fn insert(&mut self, i: usize, start: Coordinate, end: Coordinate) {
+        let (top, bottom) = if start.y <= end.y {
+            (start, end)
+        } else {
+            (end, start)
+        };
+        let slack = if is_skewed(&start, &end) { 1 } else { 0 };
+        let (min_x, max_x) = (start.x.min(end.x), start.x.max(end.x));
+        let (dx, dy) = (
+            bottom.x as i128 - top.x as i128,
+            bottom.y as i128 - top.y as i128,
+        );
+        // x of the line at y, rounded down and up.
+        let x_at = |y: i128| {
+            let y = y.clamp(top.y as i128, bottom.y as i128);
+            if dy == 0 {
+                (min_x as i128, max_x as i128)
+            } else {
+                let numerator = top.x as i128 * dy + (y - top.y as i128) * dx;
+                (floor_div(numerator, dy), ceil_div(numerator, dy))
+            }
+        };
+
+        for row in self.bucket_of(top.y)..=self.bucket_of(bottom.y) {
+            let row_top = (row as i128 * self.bucket_size as i128).max(top.y as i128);
+            let row_bottom =
+                ((row as i128 + 1) * self.bucket_size as i128 - 1).min(bottom.y as i128);
+            let (a_floor, a_ceil) = x_at(row_top - slack);
+            let (b_floor, b_ceil) = x_at(row_bottom + slack);
+            let left = (a_floor.min(b_floor) - slack).max(min_x as i128) as i64;
+            let right = (a_ceil.max(b_ceil) + slack).min(max_x as i128) as i64;
+            for col in self.bucket_of(left)..=self.bucket_of(right) {
+                self.buckets.entry((row, col)).or_default().push(i);
+            }
+        }
+    }
+
+    fn covers(&self, i: usize, point: &Coordinate) -> bool {
+        let (start, end) = self.points.point_pairs[i];
+        if is_skewed(&start, &end) {
+            Rect::new(start, end).contains(point)
+                && BresenhamIterator::new(start, end).any(|p| p == *point)
+        } else {
+            segment_contains(start, end, point)
+        }
+    }
+
+    fn touches(&self, i: usize, rect: &Rect) -> bool {
+        let (start, end) = self.points.point_pairs[i];
+        if is_skewed(&start, &end) {
+            Rect::new(start, end).intersects(rect)
+                && BresenhamIterator::new(start, end).any(|p| rect.contains(&p))
+        } else {
+            segment_touches(start, end, rect)
+        }
+    }
+
+    /// Returns the input indices of the lines through the point, in order.
+    pub fn segments_at(&self, point: &Coordinate) -> Vec<usize> {
+        let key = (self.bucket_of(point.y), self.bucket_of(point.x));
+        self.buckets
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|&i| self.covers(i, point))
+            .map(|i| self.points.indices[i])
+            .collect()
+    }
+
+    /// Returns the input indices of the lines with at least one point
+    /// inside the rect, in order.
+    pub fn segments_in(&self, rect: &Rect) -> Vec<usize> {
+        let (top, bottom) = (self.bucket_of(rect.min.y), self.bucket_of(rect.max.y));
+        let (left, right) = (self.bucket_of(rect.min.x), self.bucket_of(rect.max.x));
+        let covered = (bottom.abs_diff(top) as u128 + 1) * (right.abs_diff(left) as u128 + 1);
+        let mut candidates: Vec<usize> = if covered <= self.buckets.len() as u128 {
+            (top..=bottom)
+                .flat_map(|row| (left..=right).map(move |col| (row, col)))
+                .filter_map(|key| self.buckets.get(&key))
+                .flatten()
+                .copied()
+                .collect()
+        } else {
+            self.buckets
+                .iter()
+                .filter(|(&(row, col), _)| {
+                    (top..=bottom).contains(&row) && (left..=right).contains(&col)
+                })
+                .flat_map(|(_, lines)| lines.iter().copied())
+                .collect()
+        };
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates.retain(|&i| self.touches(i, rect));
+        candidates.iter().map(|&i| self.points.indices[i]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::read_to_points;
+    use crate::line::LineMode;
+    use crate::testing::SAMPLE;
+
+    /// Skewed lines added to the sample.
+    const SKEWED: &str = "1,0 -> 9,3
+-4,7 -> 3,-2
+8,9 -> 6,1
+";
+
+    fn points(line_mode: LineMode) -> PointsData {
+        read_to_points((SAMPLE.to_string() + SKEWED).as_bytes(), line_mode).unwrap()
+    }
+
+    fn brute_force(points: &PointsData, touches: impl Fn(&Coordinate) -> bool) -> Vec<usize> {
+        points
+            .point_pairs
+            .iter()
+            .enumerate()
+            .filter(|(_, (start, end))| points.line_mode.allows(start, end))
+            .filter(|(_, &(start, end))| BresenhamIterator::new(start, end).any(|p| touches(&p)))
+            .map(|(i, _)| points.indices[i])
+            .collect()
+    }
+
+    #[test]
+    fn test_segments_at_matches_brute_force() {
+        for line_mode in [LineMode::Orthogonal, LineMode::Diagonal, LineMode::Any] {
+            let points = points(line_mode);
+            for bucket_size in [1, 3, 100] {
+                let index = SegmentIndex::with_bucket_size(&points, bucket_size);
+                for y in -5..12 {
+                    for x in -5..12 {
+                        let point = Coordinate { x, y };
+                        assert_eq!(
+                            index.segments_at(&point),
+                            brute_force(&points, |p| *p == point),
+                            "{:?} at {:?} with buckets of {}",
+                            line_mode,
+                            point,
+                            bucket_size
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_segments_in_matches_brute_force() {
+        let points = points(LineMode::Any);
+        let index = SegmentIndex::new(&points);
+        for (a, b) in [((0, 0), (1, 1)), ((3, 5), (4, 6)), ((-10, -10), (20, 20))] {
+            let rect = Rect::new(Coordinate { x: a.0, y: a.1 }, Coordinate { x: b.0, y: b.1 });
+            assert_eq!(
+                index.segments_in(&rect),
+                brute_force(&points, |p| rect.contains(p))
+            );
+        }
+        let rect = Rect::new(Coordinate { x: 6, y: 6 }, Coordinate { x: 7, y: 7 });
+        assert_eq!(index.segments_in(&rect), vec![8, 12]);
+    }
+
+    #[test]
+    fn test_index_huge_segments() {
+        let points = PointsData::new(
+            vec![
+                (
+                    Coordinate { x: 0, y: 0 },
+                    Coordinate {
+                        x: 1 << 40,
+                        y: 1 << 40,
+                    },
+                ),
+                (
+                    Coordinate { x: 1 << 40, y: 0 },
+                    Coordinate { x: 0, y: 1 << 40 },
+                ),
+                (
+                    Coordinate {
+                        x: 5,
+                        y: -(1 << 50),
+                    },
+                    Coordinate { x: 5, y: 1 << 50 },
+                ),
+            ],
+            LineMode::Diagonal,
+        );
+        let index = SegmentIndex::new(&points);
+        assert_eq!(
+            index.segments_at(&Coordinate {
+                x: 1 << 39,
+                y: 1 << 39
+            }),
+            vec![0, 1]
+        );
+        assert_eq!(index.segments_at(&Coordinate { x: 5, y: 5 }), vec![0, 2]);
+        let rect = Rect::new(
+            Coordinate { x: 6, y: 7 },
+            Coordinate {
+                x: 1 << 20,
+                y: 1 << 20,
+            },
+        );
+        assert_eq!(index.segments_in(&rect), vec![0]);
+    }
+}
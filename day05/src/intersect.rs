@@ -0,0 +1,438 @@
+/// This file computes the overlap points of the vent lines analytically,
+/// working directly on the (start, end) pairs instead of drawing every
+/// point of every line into a Grid.
+///
+/// Every pair of lines is either parallel, in which case they can share a
+/// collinear run of points, or they cross in at most one point. The cost
+/// grows with the number of lines (and the length of the shared runs),
+/// not with the area of the grid.
+///
+/// Grid::sum_double_counts gives the same answer and is kept as a
+/// cross-check. Lines of any other slope have no exact form here, the
+/// points they share are found by rasterizing them with Bresenham's
+/// algorithm, like a Grid built with Rasterizer::Bresenham.
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::common::{Coordinate, Count, Offset, PointCount, Rect};
+use crate::file::PointsData;
+use crate::line::{BresenhamIterator, Orientation};
+
+/// The points shared by two lines.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Overlap {
+    /// The lines cross in a single point.
+    Point(Coordinate),
+    /// The lines are collinear and share every point from start to end.
+    Run(Coordinate, Coordinate),
+}
+
+impl Overlap {
+    /// Returns all points of the overlap.
+    pub fn points(&self) -> Vec<Coordinate> {
+        match *self {
+            Overlap::Point(point) => vec![point],
+            Overlap::Run(start, end) => Segment::new(start, end).points().collect(),
+        }
+    }
+}
+
+/// A line as start + step * t for t in 0..=len, with step components in -1..=1.
+struct Segment {
+    start: Coordinate,
+    step: Offset,
+    len: i64,
+}
+
+impl Segment {
+    fn new(start: Coordinate, end: Coordinate) -> Self {
+        Self {
+            start,
+            step: (end - start).signum(),
+            len: start.chebyshev(&end) as i64,
+        }
+    }
+
+    fn at(&self, t: i64) -> Coordinate {
+        self.start + self.step * t
+    }
+
+    fn points(&self) -> impl Iterator<Item = Coordinate> + '_ {
+        (0..=self.len).map(|t| self.at(t))
+    }
+
+    /// Returns t such that at(t) is the point, for a point on the line
+    /// through the segment.
+    fn project(&self, point: Coordinate) -> i64 {
+        let offset = point - self.start;
+        if self.step.x != 0 {
+            offset.x * self.step.x
+        } else {
+            offset.y * self.step.y
+        }
+    }
+
+    /// Returns t such that at(t) == point, if the point is on the segment.
+    fn param_of(&self, point: Coordinate) -> Option<i64> {
+        let offset = point - self.start;
+        if self.len == 0 {
+            return (offset == Offset::default()).then_some(0);
+        }
+        if offset.cross(self.step) != 0 {
+            return None;
+        }
+        let t = self.project(point);
+        (0..=self.len).contains(&t).then_some(t)
+    }
+}
+
+/// Returns true if the point is on the line from start to end.
+/// The line must be horizontal, vertical or at 45 degrees.
+pub fn segment_contains(start: Coordinate, end: Coordinate, point: &Coordinate) -> bool {
+    Segment::new(start, end).param_of(*point).is_some()
+}
+
+/// Returns true if any point of the line from start to end is inside the
+/// rect. The line must be horizontal, vertical or at 45 degrees.
+pub fn segment_touches(start: Coordinate, end: Coordinate, rect: &Rect) -> bool {
+    let segment = Segment::new(start, end);
+    // Narrow down the range of t for which each axis is inside the rect.
+    let axes = [
+        (segment.start.x, segment.step.x, rect.min.x, rect.max.x),
+        (segment.start.y, segment.step.y, rect.min.y, rect.max.y),
+    ];
+    let (mut lo, mut hi) = (0, segment.len);
+    for (origin, step, min, max) in axes {
+        if step == 0 {
+            if origin < min || origin > max {
+                return false;
+            }
+        } else {
+            let (t0, t1) = ((min - origin) * step, (max - origin) * step);
+            lo = lo.max(t0.min(t1));
+            hi = hi.min(t0.max(t1));
+        }
+    }
+    lo <= hi
+}
+
+/// Computes the points shared by the lines from a_start to a_end and
+/// from b_start to b_end. Lines must be horizontal, vertical or at 45 degrees.
+pub fn segment_overlap(
+    a_start: Coordinate,
+    a_end: Coordinate,
+    b_start: Coordinate,
+    b_end: Coordinate,
+) -> Option<Overlap> {
+    let a = Segment::new(a_start, a_end);
+    let b = Segment::new(b_start, b_end);
+    if a.len == 0 {
+        return b.param_of(a.start).map(|_| Overlap::Point(a_start));
+    }
+    if b.len == 0 {
+        return a.param_of(b.start).map(|_| Overlap::Point(b_start));
+    }
+
+    let det = a.step.cross(b.step);
+    let offset = b.start - a.start;
+    if det == 0 {
+        // Parallel, they overlap only when collinear.
+        if offset.cross(a.step) != 0 {
+            return None;
+        }
+        let (t0, t1) = (a.project(b.start), a.project(b.at(b.len)));
+        let lo = t0.min(t1).max(0);
+        let hi = t0.max(t1).min(a.len);
+        return match lo.cmp(&hi) {
+            std::cmp::Ordering::Less => Some(Overlap::Run(a.at(lo), a.at(hi))),
+            std::cmp::Ordering::Equal => Some(Overlap::Point(a.at(lo))),
+            std::cmp::Ordering::Greater => None,
+        };
+    }
+
+    // Solve a.start + a.step * t == b.start + b.step * s.
+    let t_num = offset.cross(b.step);
+    let s_num = offset.cross(a.step);
+    if t_num % det != 0 || s_num % det != 0 {
+        return None;
+    }
+    let (t, s) = (t_num / det, s_num / det);
+    if (0..=a.len).contains(&t) && (0..=b.len).contains(&s) {
+        Some(Overlap::Point(a.at(t)))
+    } else {
+        None
+    }
+}
+
+fn is_skewed((start, end): &(Coordinate, Coordinate)) -> bool {
+    Orientation::of(start, end) == Orientation::Other
+}
+
+/// Two lines that share points, by their input indices, see
+/// PointsData::indices.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SegmentPair {
+    pub first: usize,
+    pub second: usize,
+    /// A single Point or Run for horizontal, vertical and 45 degree
+    /// lines, one Point per shared point for lines of any other slope.
+    pub shared: Vec<Overlap>,
+}
+
+/// Lists every pair of lines allowed by the line mode of the points that
+/// share at least one point, ordered by first and then second index.
+pub fn intersecting_pairs(points: &PointsData) -> Vec<SegmentPair> {
+    let segments: Vec<_> = points
+        .indices
+        .iter()
+        .copied()
+        .zip(&points.point_pairs)
+        .filter(|(_, (start, end))| points.line_mode.allows(start, end))
+        .collect();
+    let mut pairs = Vec::new();
+    for (i, &(first, a)) in segments.iter().enumerate() {
+        for &(second, b) in &segments[i + 1..] {
+            let shared: Vec<_> = if is_skewed(a) || is_skewed(b) {
+                let a_points: HashSet<_> = BresenhamIterator::new(a.0, a.1).collect();
+                BresenhamIterator::new(b.0, b.1)
+                    .filter(|p| a_points.contains(p))
+                    .map(Overlap::Point)
+                    .collect()
+            } else {
+                segment_overlap(a.0, a.1, b.0, b.1).into_iter().collect()
+            };
+            if !shared.is_empty() {
+                pairs.push(SegmentPair {
+                    first,
+                    second,
+                    shared,
+                });
+            }
+        }
+    }
+    pairs
+}
+
+/// Returns every point covered by at least two of the lines allowed by
+/// the line mode of the points.
+pub fn overlap_points(points: &PointsData) -> HashSet<Coordinate> {
+    intersecting_pairs(points)
+        .iter()
+        .flat_map(|pair| pair.shared.iter().flat_map(Overlap::points))
+        .collect()
+}
+
+/// Writes every intersecting pair of lines with its shared points, each
+/// point with the number of lines covering it. Lines are numbered from 1
+/// by their input index, so a line read from a text file by its line, e.g.
+///
+/// Lines 1 (0,9 -> 5,9) and 7 (0,9 -> 2,9):
+///   Run (0,9) -> (2,9)
+///   Point (0,9): Count=2
+///   ...
+pub fn write_pair_report(points: &PointsData, mut writer: impl Write) -> io::Result<()> {
+    let pairs = intersecting_pairs(points);
+    // Every line covering a point shared by two lines shows up in a pair with it.
+    let mut covering: HashMap<Coordinate, BTreeSet<usize>> = HashMap::new();
+    for pair in &pairs {
+        for point in pair.shared.iter().flat_map(Overlap::points) {
+            let lines = covering.entry(point).or_default();
+            lines.insert(pair.first);
+            lines.insert(pair.second);
+        }
+    }
+    let lines: HashMap<usize, (Coordinate, Coordinate)> = points
+        .indices
+        .iter()
+        .copied()
+        .zip(points.point_pairs.iter().copied())
+        .collect();
+    let describe = |index: usize| {
+        let (start, end) = lines[&index];
+        format!(
+            "{} ({},{} -> {},{})",
+            index + 1,
+            start.x,
+            start.y,
+            end.x,
+            end.y
+        )
+    };
+    for pair in &pairs {
+        writeln!(
+            writer,
+            "Lines {} and {}:",
+            describe(pair.first),
+            describe(pair.second)
+        )?;
+        for overlap in &pair.shared {
+            if let Overlap::Run(start, end) = overlap {
+                writeln!(
+                    writer,
+                    "  Run ({},{}) -> ({},{})",
+                    start.x, start.y, end.x, end.y
+                )?;
+            }
+            for point in overlap.points() {
+                let point_count = PointCount {
+                    point,
+                    count: Count(covering[&point].len()),
+                };
+                writeln!(writer, "  {}", point_count)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Counts the points where at least two lines overlap, the same
+/// number as Grid::sum_double_counts.
+pub fn count_overlaps(points: &PointsData) -> usize {
+    overlap_points(points).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::read_to_points;
+    use crate::grid::Grid;
+    use crate::line::{LineMode, Rasterizer};
+    use crate::testing::SAMPLE;
+
+    fn c(x: i64, y: i64) -> Coordinate {
+        Coordinate { x, y }
+    }
+
+    fn sample(line_mode: LineMode) -> PointsData {
+        read_to_points(SAMPLE.as_bytes(), line_mode).unwrap()
+    }
+
+    #[test]
+    fn test_segment_overlap_crossing() {
+        assert_eq!(
+            segment_overlap(c(0, 0), c(4, 4), c(4, 0), c(0, 4)),
+            Some(Overlap::Point(c(2, 2)))
+        );
+        assert_eq!(
+            segment_overlap(c(0, 2), c(4, 2), c(3, 0), c(3, 5)),
+            Some(Overlap::Point(c(3, 2)))
+        );
+        // Diagonals crossing between lattice points share nothing.
+        assert_eq!(segment_overlap(c(0, 0), c(3, 3), c(3, 0), c(0, 3)), None);
+        assert_eq!(segment_overlap(c(0, 0), c(1, 1), c(5, 0), c(0, 5)), None);
+    }
+
+    #[test]
+    fn test_segment_overlap_collinear() {
+        assert_eq!(
+            segment_overlap(c(0, 9), c(5, 9), c(2, 9), c(0, 9)),
+            Some(Overlap::Run(c(0, 9), c(2, 9)))
+        );
+        assert_eq!(
+            segment_overlap(c(5, 5), c(1, 1), c(0, 0), c(3, 3)),
+            Some(Overlap::Run(c(3, 3), c(1, 1)))
+        );
+        assert_eq!(
+            segment_overlap(c(0, 0), c(2, 0), c(2, 0), c(4, 0)),
+            Some(Overlap::Point(c(2, 0)))
+        );
+        assert_eq!(segment_overlap(c(0, 0), c(2, 0), c(3, 0), c(4, 0)), None);
+        assert_eq!(segment_overlap(c(0, 0), c(2, 0), c(0, 1), c(2, 1)), None);
+    }
+
+    #[test]
+    fn test_segment_overlap_single_point_line() {
+        assert_eq!(
+            segment_overlap(c(1, 1), c(1, 1), c(0, 0), c(2, 2)),
+            Some(Overlap::Point(c(1, 1)))
+        );
+        assert_eq!(segment_overlap(c(1, 0), c(1, 0), c(0, 0), c(2, 2)), None);
+    }
+
+    #[test]
+    fn test_count_overlaps_matches_grid() {
+        assert_eq!(count_overlaps(&sample(LineMode::Orthogonal)), 5);
+        assert_eq!(count_overlaps(&sample(LineMode::Diagonal)), 12);
+        for line_mode in [LineMode::Orthogonal, LineMode::Diagonal] {
+            let grid = Grid::new(sample(line_mode));
+            assert_eq!(
+                count_overlaps(&sample(line_mode)),
+                grid.sum_double_counts() as usize
+            );
+        }
+    }
+
+    #[test]
+    fn test_count_overlaps_any_slope() {
+        let points = PointsData::new(
+            vec![(c(0, 0), c(4, 2)), (c(0, 1), c(4, 1)), (c(4, 0), c(0, 4))],
+            LineMode::Any,
+        );
+        let grid = Grid::new(PointsData::new(points.point_pairs.clone(), LineMode::Any));
+        assert_eq!(grid.rasterizer(), Rasterizer::Bresenham);
+        assert_eq!(count_overlaps(&points), grid.sum_double_counts() as usize);
+
+        let points = PointsData::new(vec![(c(0, 0), c(4, 2)), (c(0, 1), c(4, 1))], LineMode::Any);
+        assert_eq!(count_overlaps(&points), 2);
+        assert_eq!(Grid::new(points).sum_double_counts(), 2);
+    }
+
+    #[test]
+    fn test_intersecting_pairs() {
+        let pairs = intersecting_pairs(&sample(LineMode::Orthogonal));
+        assert_eq!(
+            pairs,
+            vec![
+                SegmentPair {
+                    first: 0,
+                    second: 6,
+                    shared: vec![Overlap::Run(c(0, 9), c(2, 9))],
+                },
+                SegmentPair {
+                    first: 2,
+                    second: 4,
+                    shared: vec![Overlap::Point(c(7, 4))],
+                },
+                SegmentPair {
+                    first: 2,
+                    second: 7,
+                    shared: vec![Overlap::Point(c(3, 4))],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_intersecting_pairs_any_slope() {
+        let points = PointsData::new(vec![(c(0, 0), c(4, 2)), (c(0, 1), c(4, 1))], LineMode::Any);
+        let pairs = intersecting_pairs(&points);
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(
+            pairs[0].shared,
+            vec![Overlap::Point(c(1, 1)), Overlap::Point(c(2, 1))]
+        );
+    }
+
+    #[test]
+    fn test_write_pair_report() {
+        let points = PointsData::new(
+            vec![(c(0, 0), c(4, 0)), (c(2, 0), c(6, 0)), (c(3, 0), c(3, 2))],
+            LineMode::Orthogonal,
+        );
+        let mut report = Vec::new();
+        write_pair_report(&points, &mut report).unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "Lines 1 (0,0 -> 4,0) and 2 (2,0 -> 6,0):
+  Run (2,0) -> (4,0)
+  Point (2,0): Count=2
+  Point (3,0): Count=3
+  Point (4,0): Count=2
+Lines 1 (0,0 -> 4,0) and 3 (3,0 -> 3,2):
+  Point (3,0): Count=3
+Lines 2 (2,0 -> 6,0) and 3 (3,0 -> 3,2):
+  Point (3,0): Count=3
+"
+        );
+    }
+}
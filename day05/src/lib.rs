@@ -1,4 +1,19 @@
+pub mod analytics;
+pub mod cli;
 pub mod common;
+pub mod compress;
+pub mod diff;
+pub mod error;
 pub mod file;
+pub mod formats;
 pub mod grid;
+pub mod image;
+pub mod index;
+pub mod intersect;
 pub mod line;
+pub mod render;
+pub mod storage;
+pub mod svg;
+
+#[cfg(test)]
+mod testing;
@@ -5,40 +5,81 @@
 ///
 /// The LineIterator struct is an iterator that generates all coordinates
 /// from the start to the end of a line. Order doesn't matter.
-use crate::common::Coordinate;
+///
+/// LineIterator is only correct for horizontal, vertical and 45 degree
+/// lines, the BresenhamIterator struct rasterizes lines of any slope.
+use crate::common::{Coordinate, Offset};
+
+/// Which line segments are kept when reading vent lines.
+/// Part 1 only considers horizontal and vertical lines, part 2 also
+/// considers diagonal lines at exactly 45 degrees. Any keeps lines of
+/// any slope, they need the Bresenham rasterizer to be drawn correctly.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum LineMode {
+    #[default]
+    Orthogonal,
+    Diagonal,
+    Any,
+}
+
+impl LineMode {
+    /// Returns true if the segment from start to end is allowed in this mode.
+    pub fn allows(&self, start: &Coordinate, end: &Coordinate) -> bool {
+        matches!(
+            (self, Orientation::of(start, end)),
+            (_, Orientation::Horizontal | Orientation::Vertical)
+                | (LineMode::Diagonal, Orientation::Diagonal)
+                | (LineMode::Any, _)
+        )
+    }
+}
 
-pub struct Step {
-    x: i32,
-    y: i32,
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Orientation {
+    Horizontal,
+    Vertical,
+    Diagonal,
+    Other,
+}
+
+impl Orientation {
+    /// Classifies the segment from start to end. A single point counts as horizontal.
+    pub fn of(start: &Coordinate, end: &Coordinate) -> Self {
+        if start.y == end.y {
+            Orientation::Horizontal
+        } else if start.x == end.x {
+            Orientation::Vertical
+        } else if start.x.abs_diff(end.x) == start.y.abs_diff(end.y) {
+            Orientation::Diagonal
+        } else {
+            Orientation::Other
+        }
+    }
 }
 
 pub struct LineIterator {
     current: Coordinate,
     end: Coordinate,
-    step: Step,
+    step: Offset,
     done: bool,
 }
 
 impl LineIterator {
     pub fn new(start: Coordinate, end: Coordinate) -> Self {
-        let step = Step {
-            x: if start.x <= end.x { 1 } else { -1 },
-            y: if start.y <= end.y { 1 } else { -1 },
-        };
         Self {
             current: start,
             end,
-            step,
+            step: (end - start).signum(),
             done: false,
         }
     }
 
     fn update_current(&mut self) {
         if self.current.x != self.end.x {
-            self.current.x = (self.current.x as i32 + self.step.x) as usize;
+            self.current.x += self.step.x;
         }
         if self.current.y != self.end.y {
-            self.current.y = (self.current.y as i32 + self.step.y) as usize;
+            self.current.y += self.step.y;
         }
     }
 }
@@ -59,6 +100,97 @@ impl Iterator for LineIterator {
         }
     }
 }
+
+/// Generates the coordinates of a line of any slope with Bresenham's
+/// algorithm, one coordinate per step along the longer axis. The line is
+/// always walked from its smaller endpoint, so a line and its reverse
+/// produce the same coordinates.
+pub struct BresenhamIterator {
+    current: Coordinate,
+    end: Coordinate,
+    step: Offset,
+    delta: Offset,
+    error: i64,
+    done: bool,
+}
+
+impl BresenhamIterator {
+    pub fn new(start: Coordinate, end: Coordinate) -> Self {
+        let (start, end) = if (end.x, end.y) < (start.x, start.y) {
+            (end, start)
+        } else {
+            (start, end)
+        };
+        let difference = end - start;
+        let delta = Offset::new(difference.x.abs(), -difference.y.abs());
+        Self {
+            current: start,
+            end,
+            step: difference.signum(),
+            error: delta.x + delta.y,
+            delta,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for BresenhamIterator {
+    type Item = Coordinate;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.current;
+        if self.current == self.end {
+            self.done = true;
+        } else {
+            let error2 = 2 * self.error;
+            if error2 >= self.delta.y {
+                self.error += self.delta.y;
+                self.current.x += self.step.x;
+            }
+            if error2 <= self.delta.x {
+                self.error += self.delta.x;
+                self.current.y += self.step.y;
+            }
+        }
+        Some(result)
+    }
+}
+
+/// How a line is turned into the coordinates it covers.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Rasterizer {
+    /// LineIterator, for horizontal, vertical and 45 degree lines.
+    #[default]
+    Stepped,
+    /// BresenhamIterator, for lines of any slope.
+    Bresenham,
+}
+
+impl Rasterizer {
+    /// Returns the rasterizer that draws every line the mode allows
+    /// correctly: Bresenham for Any, Stepped otherwise.
+    pub fn for_mode(line_mode: LineMode) -> Self {
+        match line_mode {
+            LineMode::Any => Rasterizer::Bresenham,
+            _ => Rasterizer::Stepped,
+        }
+    }
+
+    pub fn points(
+        &self,
+        start: Coordinate,
+        end: Coordinate,
+    ) -> Box<dyn Iterator<Item = Coordinate>> {
+        match self {
+            Rasterizer::Stepped => Box::new(LineIterator::new(start, end)),
+            Rasterizer::Bresenham => Box::new(BresenhamIterator::new(start, end)),
+        }
+    }
+}
+
 pub struct Line {
     pub start: Coordinate,
     pub end: Coordinate,
@@ -74,6 +206,22 @@ impl Line {
             line_coordinates,
         }
     }
+
+    /// Returns the number of steps from start to end along the longer axis,
+    /// one less than the number of coordinates on the line.
+    pub fn length(&self) -> u64 {
+        self.start.chebyshev(&self.end)
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        Orientation::of(&self.start, &self.end)
+    }
+
+    /// Returns the step from one coordinate to the next, for horizontal,
+    /// vertical and 45 degree lines.
+    pub fn direction(&self) -> Offset {
+        (self.end - self.start).signum()
+    }
 }
 
 #[cfg(test)]
@@ -106,4 +254,101 @@ mod tests {
         ];
         assert_eq!(line.line_coordinates, expected_coordinates);
     }
+
+    #[test]
+    fn test_to_line_coords_diagonal() {
+        let line = Line::new(Coordinate { x: 9, y: 7 }, Coordinate { x: 7, y: 9 });
+        let expected_coordinates = vec![
+            Coordinate { x: 9, y: 7 },
+            Coordinate { x: 8, y: 8 },
+            Coordinate { x: 7, y: 9 },
+        ];
+        assert_eq!(line.line_coordinates, expected_coordinates);
+    }
+
+    #[test]
+    fn test_line_geometry() {
+        let line = Line::new(Coordinate { x: 9, y: 7 }, Coordinate { x: 7, y: 9 });
+        assert_eq!(line.length(), 2);
+        assert_eq!(line.length() as usize + 1, line.line_coordinates.len());
+        assert_eq!(line.orientation(), Orientation::Diagonal);
+        assert_eq!(line.direction(), Offset::new(-1, 1));
+
+        let line = Line::new(Coordinate { x: 3, y: 4 }, Coordinate { x: 3, y: 0 });
+        assert_eq!(line.length(), 4);
+        assert_eq!(line.orientation(), Orientation::Vertical);
+        assert_eq!(line.direction(), Offset::UP);
+    }
+
+    #[test]
+    fn test_line_mode_allows() {
+        let start = Coordinate { x: 1, y: 1 };
+        let diagonal = Coordinate { x: 3, y: 3 };
+        let skewed = Coordinate { x: 3, y: 4 };
+        assert!(LineMode::Orthogonal.allows(&start, &Coordinate { x: 1, y: 3 }));
+        assert!(!LineMode::Orthogonal.allows(&start, &diagonal));
+        assert!(LineMode::Diagonal.allows(&start, &diagonal));
+        assert!(!LineMode::Diagonal.allows(&start, &skewed));
+    }
+
+    #[test]
+    fn test_orientation_of() {
+        let start = Coordinate { x: 2, y: 2 };
+        let of = |x, y| Orientation::of(&start, &Coordinate { x, y });
+        assert_eq!(of(2, 2), Orientation::Horizontal);
+        assert_eq!(of(5, 2), Orientation::Horizontal);
+        assert_eq!(of(2, 0), Orientation::Vertical);
+        assert_eq!(of(0, 4), Orientation::Diagonal);
+        assert_eq!(of(3, 4), Orientation::Other);
+    }
+
+    #[test]
+    fn test_to_line_coords_negative() {
+        let line = Line::new(Coordinate { x: -1, y: 1 }, Coordinate { x: 1, y: -1 });
+        let expected_coordinates = vec![
+            Coordinate { x: -1, y: 1 },
+            Coordinate { x: 0, y: 0 },
+            Coordinate { x: 1, y: -1 },
+        ];
+        assert_eq!(line.line_coordinates, expected_coordinates);
+    }
+
+    #[test]
+    fn test_bresenham_any_slope() {
+        let start = Coordinate { x: 0, y: 0 };
+        let end = Coordinate { x: 4, y: 2 };
+        let expected_coordinates = vec![
+            Coordinate { x: 0, y: 0 },
+            Coordinate { x: 1, y: 1 },
+            Coordinate { x: 2, y: 1 },
+            Coordinate { x: 3, y: 2 },
+            Coordinate { x: 4, y: 2 },
+        ];
+        let coordinates: Vec<_> = BresenhamIterator::new(start, end).collect();
+        assert_eq!(coordinates, expected_coordinates);
+        let reversed: Vec<_> = BresenhamIterator::new(end, start).collect();
+        assert_eq!(reversed, expected_coordinates);
+
+        // The stepped iterator bends instead.
+        let stepped: Vec<_> = LineIterator::new(start, end).collect();
+        assert_eq!(stepped[2], Coordinate { x: 2, y: 2 });
+    }
+
+    #[test]
+    fn test_bresenham_matches_stepped_lines() {
+        let start = Coordinate { x: 5, y: 1 };
+        for end in [
+            Coordinate { x: 1, y: 1 },
+            Coordinate { x: 5, y: -3 },
+            Coordinate { x: 1, y: 5 },
+            Coordinate { x: 9, y: 5 },
+            Coordinate { x: 5, y: 1 },
+        ] {
+            let mut stepped: Vec<_> = Rasterizer::Stepped.points(start, end).collect();
+            let mut bresenham: Vec<_> = Rasterizer::Bresenham.points(start, end).collect();
+            stepped.sort_by_key(|point| (point.x, point.y));
+            bresenham.sort_by_key(|point| (point.x, point.y));
+            assert_eq!(stepped, bresenham);
+        }
+    }
 }
@@ -1,10 +1,19 @@
-use day05::file::read_file_to_points;
-use day05::grid::Grid;
+use std::env;
+use std::io;
+use std::process;
+
+use day05::cli::{parse_args, run, USAGE};
 
 fn main() {
-    let path = String::from("data/data1.txt");
-    let points = read_file_to_points(&path).unwrap();
-    let grid = Grid::new(points);
-    // println!("{}", grid)
-    print!("{}", grid.sum_double_counts())
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(err) = run(&options, io::stdin().lock(), io::stdout().lock()) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
 }
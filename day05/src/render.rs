@@ -0,0 +1,183 @@
+/// This file renders a part of a Grid as text that fits in a terminal.
+///
+/// Unlike the Display of Grid, which prints every cell, the renderer
+/// takes a viewport, can print the x coordinates above and the y
+/// coordinates left of the cells, and can downsample the grid so each
+/// character stands for a block of factor x factor cells, combined by
+/// taking the max or the sum of their counts.
+///
+/// Each cell is one character: '.' for 0, the digit for 1 to 9 and '#'
+/// for anything above 9, so the columns stay aligned.
+use std::collections::HashMap;
+
+use crate::common::Rect;
+use crate::grid::Grid;
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Aggregation {
+    #[default]
+    Max,
+    Sum,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RenderOptions {
+    /// Cells to render, None to render the bounds of the grid.
+    pub viewport: Option<Rect>,
+    /// Print the coordinates above and left of the cells.
+    pub rulers: bool,
+    /// Each character stands for factor x factor cells.
+    pub factor: usize,
+    pub aggregation: Aggregation,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            viewport: None,
+            rulers: false,
+            factor: 1,
+            aggregation: Aggregation::Max,
+        }
+    }
+}
+
+/// Ruler labels are printed every this many characters.
+const RULER_STEP: usize = 10;
+
+pub(crate) fn cell_char(count: usize) -> char {
+    match count {
+        0 => '.',
+        1..=9 => char::from_digit(count as u32, 10).unwrap(),
+        _ => '#',
+    }
+}
+
+impl Grid {
+    /// Renders the cells of the viewport with the options.
+    pub fn render(&self, options: &RenderOptions) -> String {
+        let viewport = options.viewport.unwrap_or(self.bounds());
+        let factor = options.factor.max(1);
+        let columns = viewport.width().div_ceil(factor);
+        let rows = viewport.height().div_ceil(factor);
+
+        // Only visit the cells with a count, the viewport can be huge.
+        let mut blocks: HashMap<(usize, usize), usize> = HashMap::new();
+        for point_count in self.point_counts() {
+            if !viewport.contains(&point_count.point) {
+                continue;
+            }
+            let row = point_count.point.y.abs_diff(viewport.min.y) as usize / factor;
+            let col = point_count.point.x.abs_diff(viewport.min.x) as usize / factor;
+            let block = blocks.entry((row, col)).or_insert(0);
+            *block = match options.aggregation {
+                Aggregation::Max => (*block).max(point_count.count.0),
+                Aggregation::Sum => *block + point_count.count.0,
+            };
+        }
+
+        let x_at = |col: usize| viewport.min.x + (col * factor) as i64;
+        let y_at = |row: usize| viewport.min.y + (row * factor) as i64;
+        let gutter = if options.rulers {
+            (0..rows)
+                .map(|row| y_at(row).to_string().len())
+                .max()
+                .unwrap_or(0)
+                + 1
+        } else {
+            0
+        };
+
+        let mut output = String::new();
+        if options.rulers {
+            let mut labels = String::new();
+            let mut ticks = String::new();
+            for col in 0..columns {
+                if col % RULER_STEP == 0 {
+                    labels.push_str(&x_at(col).to_string());
+                    ticks.push('|');
+                } else {
+                    ticks.push('-');
+                }
+                // Labels that run into the next one are cut off.
+                let limit = (col / RULER_STEP + 1) * RULER_STEP - 1;
+                labels.truncate(limit.min(labels.len()));
+                while labels.len() < col + 1 {
+                    labels.push(' ');
+                }
+            }
+            output.push_str(&format!("{:gutter$}{}\n", "", labels.trim_end()));
+            output.push_str(&format!("{:gutter$}{}\n", "", ticks));
+        }
+        for row in 0..rows {
+            if options.rulers {
+                output.push_str(&format!("{:>width$} ", y_at(row), width = gutter - 1));
+            }
+            for col in 0..columns {
+                output.push(cell_char(blocks.get(&(row, col)).copied().unwrap_or(0)));
+            }
+            output.push('\n');
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Coordinate;
+    use crate::line::LineMode;
+    use crate::testing::sample_grid;
+
+    #[test]
+    fn test_render_default_matches_display() {
+        let grid = sample_grid(LineMode::Diagonal);
+        assert_eq!(grid.render(&RenderOptions::default()), grid.to_string());
+    }
+
+    #[test]
+    fn test_render_viewport() {
+        let options = RenderOptions {
+            viewport: Some(Rect::new(
+                Coordinate { x: 3, y: 3 },
+                Coordinate { x: 7, y: 5 },
+            )),
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            sample_grid(LineMode::Diagonal).render(&options),
+            "1.2.2\n23132\n1.2..\n"
+        );
+    }
+
+    #[test]
+    fn test_render_rulers() {
+        let options = RenderOptions {
+            viewport: Some(Rect::new(
+                Coordinate { x: 8, y: 8 },
+                Coordinate { x: 12, y: 10 },
+            )),
+            rulers: true,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            sample_grid(LineMode::Diagonal).render(&options),
+            "   8\n   |----\n 8 1....\n 9 .....\n10 .....\n"
+        );
+    }
+
+    #[test]
+    fn test_render_downsample() {
+        let options = RenderOptions {
+            factor: 5,
+            ..RenderOptions::default()
+        };
+        assert_eq!(sample_grid(LineMode::Diagonal).render(&options), "33\n22\n");
+        let options = RenderOptions {
+            factor: 5,
+            aggregation: Aggregation::Sum,
+            ..RenderOptions::default()
+        };
+        assert_eq!(sample_grid(LineMode::Diagonal).render(&options), "##\n#6\n");
+    }
+}
@@ -0,0 +1,394 @@
+/// This file defines the storage backends used by the Grid struct.
+/// The CellStorage trait abstracts how the count at each cell is kept,
+/// so the Grid can either allocate every cell up front (DenseStorage)
+/// or only the cells that are actually touched by a line (SparseStorage).
+///
+/// Cells are addressed by (row, col), i.e. (y, x), matching the layout
+/// of the Grid.
+use std::collections::HashMap;
+
+pub trait CellStorage: Send {
+    /// Returns the count at the given cell, 0 if it was never touched.
+    fn get(&self, row: usize, col: usize) -> usize;
+
+    /// Adds amount to the count at the given cell, returning the new count.
+    fn add(&mut self, row: usize, col: usize, amount: usize) -> usize;
+
+    /// Adds one to the count at the given cell, returning the new count.
+    fn increment(&mut self, row: usize, col: usize) -> usize {
+        self.add(row, col, 1)
+    }
+
+    /// Takes one from the count at the given cell, returning the new count.
+    /// A cell at 0 stays at 0.
+    fn decrement(&mut self, row: usize, col: usize) -> usize;
+
+    /// Iterates over all cells with a count above 0 as (row, col, count).
+    fn iter_nonzero(&self) -> Box<dyn Iterator<Item = (usize, usize, usize)> + '_>;
+}
+
+/// A cell counter type for DenseStorage.
+pub trait Counter: Copy + Default + Send + 'static {
+    /// The highest count this counter can hold.
+    const MAX: usize;
+
+    fn get(self) -> usize;
+
+    /// Creates the counter for value, which must be at most MAX.
+    fn from_count(value: usize) -> Self;
+}
+
+impl Counter for u8 {
+    const MAX: usize = u8::MAX as usize;
+
+    fn get(self) -> usize {
+        self as usize
+    }
+
+    fn from_count(value: usize) -> Self {
+        value as u8
+    }
+}
+
+impl Counter for u16 {
+    const MAX: usize = u16::MAX as usize;
+
+    fn get(self) -> usize {
+        self as usize
+    }
+
+    fn from_count(value: usize) -> Self {
+        value as u16
+    }
+}
+
+impl Counter for u32 {
+    const MAX: usize = u32::MAX as usize;
+
+    fn get(self) -> usize {
+        self as usize
+    }
+
+    fn from_count(value: usize) -> Self {
+        value as u32
+    }
+}
+
+impl Counter for usize {
+    const MAX: usize = usize::MAX;
+
+    fn get(self) -> usize {
+        self
+    }
+
+    fn from_count(value: usize) -> Self {
+        value
+    }
+}
+
+fn widen<A: Counter, B: Counter>(cells: &[A]) -> Vec<B> {
+    cells.iter().map(|cell| B::from_count(cell.get())).collect()
+}
+
+/// The cells of a DenseStorage in one flat buffer, with the smallest
+/// counter type that holds every count.
+enum Counters {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    Usize(Vec<usize>),
+}
+
+impl Counters {
+    fn get(&self, index: usize) -> usize {
+        match self {
+            Counters::U8(cells) => cells[index].get(),
+            Counters::U16(cells) => cells[index].get(),
+            Counters::U32(cells) => cells[index].get(),
+            Counters::Usize(cells) => cells[index].get(),
+        }
+    }
+
+    /// Sets the count, first promoting every cell to a wider counter
+    /// when the value doesn't fit the current one.
+    fn set(&mut self, index: usize, value: usize) {
+        if value > self.max() {
+            self.promote(value);
+        }
+        match self {
+            Counters::U8(cells) => cells[index] = u8::from_count(value),
+            Counters::U16(cells) => cells[index] = u16::from_count(value),
+            Counters::U32(cells) => cells[index] = u32::from_count(value),
+            Counters::Usize(cells) => cells[index] = value,
+        }
+    }
+
+    fn max(&self) -> usize {
+        match self {
+            Counters::U8(_) => <u8 as Counter>::MAX,
+            Counters::U16(_) => <u16 as Counter>::MAX,
+            Counters::U32(_) => <u32 as Counter>::MAX,
+            Counters::Usize(_) => <usize as Counter>::MAX,
+        }
+    }
+
+    fn promote(&mut self, value: usize) {
+        *self = match self {
+            Counters::U8(cells) if value <= <u16 as Counter>::MAX => Counters::U16(widen(cells)),
+            Counters::U8(cells) if value <= <u32 as Counter>::MAX => Counters::U32(widen(cells)),
+            Counters::U8(cells) => Counters::Usize(widen(cells)),
+            Counters::U16(cells) if value <= <u32 as Counter>::MAX => Counters::U32(widen(cells)),
+            Counters::U16(cells) => Counters::Usize(widen(cells)),
+            Counters::U32(cells) => Counters::Usize(widen(cells)),
+            Counters::Usize(_) => return,
+        };
+    }
+
+    fn bytes_per_cell(&self) -> usize {
+        match self {
+            Counters::U8(_) => 1,
+            Counters::U16(_) => 2,
+            Counters::U32(_) => 4,
+            Counters::Usize(_) => std::mem::size_of::<usize>(),
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        match self {
+            Counters::U8(cells) => Box::new(cells.iter().map(|cell| cell.get())),
+            Counters::U16(cells) => Box::new(cells.iter().map(|cell| cell.get())),
+            Counters::U32(cells) => Box::new(cells.iter().map(|cell| cell.get())),
+            Counters::Usize(cells) => Box::new(cells.iter().copied()),
+        }
+    }
+}
+
+/// Keeps every cell of a width x height grid in memory, row after row
+/// in one flat buffer. Cells start as u8 counters and the whole buffer
+/// is promoted to u16, u32 and usize as soon as a count needs it, so
+/// counts are always exact.
+pub struct DenseStorage {
+    width: usize,
+    cells: Counters,
+}
+
+impl DenseStorage {
+    /// Panics if width * height overflows, Backend::choose never picks
+    /// Dense for such a grid.
+    pub fn new(width: usize, height: usize) -> Self {
+        let cells = width
+            .checked_mul(height)
+            .expect("dense grid has too many cells");
+        Self {
+            width,
+            cells: Counters::U8(vec![0u8; cells]),
+        }
+    }
+
+    /// Returns the size in bytes of the counter currently used for each cell.
+    pub fn bytes_per_cell(&self) -> usize {
+        self.cells.bytes_per_cell()
+    }
+}
+
+impl CellStorage for DenseStorage {
+    fn get(&self, row: usize, col: usize) -> usize {
+        self.cells.get(row * self.width + col)
+    }
+
+    fn add(&mut self, row: usize, col: usize, amount: usize) -> usize {
+        let index = row * self.width + col;
+        let count = self.cells.get(index).saturating_add(amount);
+        self.cells.set(index, count);
+        count
+    }
+
+    fn decrement(&mut self, row: usize, col: usize) -> usize {
+        let index = row * self.width + col;
+        let count = self.cells.get(index).saturating_sub(1);
+        self.cells.set(index, count);
+        count
+    }
+
+    fn iter_nonzero(&self) -> Box<dyn Iterator<Item = (usize, usize, usize)> + '_> {
+        let width = self.width;
+        Box::new(
+            self.cells
+                .iter()
+                .enumerate()
+                .filter(|&(_, count)| count > 0)
+                .map(move |(index, count)| (index / width, index % width, count)),
+        )
+    }
+}
+
+/// Only keeps the cells that have been touched, keyed by (row, col).
+#[derive(Default)]
+pub struct SparseStorage {
+    cells: HashMap<(usize, usize), usize>,
+}
+
+impl SparseStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CellStorage for SparseStorage {
+    fn get(&self, row: usize, col: usize) -> usize {
+        self.cells.get(&(row, col)).copied().unwrap_or(0)
+    }
+
+    fn add(&mut self, row: usize, col: usize, amount: usize) -> usize {
+        let count = self.cells.entry((row, col)).or_insert(0);
+        *count += amount;
+        *count
+    }
+
+    fn decrement(&mut self, row: usize, col: usize) -> usize {
+        match self.cells.get_mut(&(row, col)) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                *count
+            }
+            Some(_) => {
+                self.cells.remove(&(row, col));
+                0
+            }
+            None => 0,
+        }
+    }
+
+    fn iter_nonzero(&self) -> Box<dyn Iterator<Item = (usize, usize, usize)> + '_> {
+        Box::new(
+            self.cells
+                .iter()
+                .map(|(&(row, col), &count)| (row, col, count)),
+        )
+    }
+}
+
+/// Which CellStorage a Grid uses.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Backend {
+    Dense,
+    Sparse,
+}
+
+/// Grids with at most this many cells are always dense, they are cheap anyway.
+const SMALL_GRID_CELLS: usize = 1 << 16;
+/// A grid is dense when its area is at most this many times the number
+/// of points drawn on it.
+const DENSITY_FACTOR: usize = 8;
+/// Grids with more cells than this are always sparse, however full they
+/// are, so a huge bounding box can't exhaust memory.
+const MAX_DENSE_CELLS: usize = 1 << 28;
+
+impl Backend {
+    /// Picks a backend from the bounding box and the number of points that
+    /// will be drawn. Dense is used unless the grid would be mostly empty
+    /// or too large to allocate.
+    pub fn choose(width: usize, height: usize, points: usize) -> Self {
+        match width.checked_mul(height) {
+            Some(area) if area > MAX_DENSE_CELLS => Backend::Sparse,
+            Some(area) if area <= SMALL_GRID_CELLS => Backend::Dense,
+            Some(area) if area / DENSITY_FACTOR <= points => Backend::Dense,
+            _ => Backend::Sparse,
+        }
+    }
+
+    /// Returns false for a dense storage with more than MAX_DENSE_CELLS cells.
+    pub fn fits(&self, width: usize, height: usize) -> bool {
+        match self {
+            Backend::Dense => width
+                .checked_mul(height)
+                .is_some_and(|area| area <= MAX_DENSE_CELLS),
+            Backend::Sparse => true,
+        }
+    }
+
+    pub fn storage(&self, width: usize, height: usize) -> Box<dyn CellStorage> {
+        match self {
+            Backend::Dense => Box::new(DenseStorage::new(width, height)),
+            Backend::Sparse => Box::new(SparseStorage::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(storage: &mut dyn CellStorage) {
+        storage.increment(0, 1);
+        storage.increment(2, 3);
+        assert_eq!(storage.increment(2, 3), 2);
+        storage.increment(1, 2);
+        assert_eq!(storage.decrement(1, 2), 0);
+        assert_eq!(storage.decrement(1, 2), 0);
+    }
+
+    #[test]
+    fn test_dense_and_sparse_agree() {
+        let mut dense = DenseStorage::new(4, 3);
+        let mut sparse = SparseStorage::new();
+        fill(&mut dense);
+        fill(&mut sparse);
+
+        assert_eq!(dense.get(2, 3), 2);
+        assert_eq!(sparse.get(2, 3), 2);
+        assert_eq!(sparse.get(1, 1), 0);
+
+        let mut dense_cells: Vec<_> = dense.iter_nonzero().collect();
+        let mut sparse_cells: Vec<_> = sparse.iter_nonzero().collect();
+        dense_cells.sort();
+        sparse_cells.sort();
+        assert_eq!(dense_cells, vec![(0, 1, 1), (2, 3, 2)]);
+        assert_eq!(dense_cells, sparse_cells);
+    }
+
+    #[test]
+    fn test_dense_promotes_counters() {
+        let mut dense = DenseStorage::new(3, 2);
+        assert_eq!(dense.bytes_per_cell(), 1);
+        for _ in 0..255 {
+            dense.increment(1, 2);
+        }
+        dense.increment(0, 0);
+        assert_eq!(dense.bytes_per_cell(), 1);
+        assert_eq!(dense.increment(1, 2), 256);
+        assert_eq!(dense.bytes_per_cell(), 2);
+        assert_eq!(dense.get(1, 2), 256);
+        assert_eq!(dense.get(0, 0), 1);
+
+        assert_eq!(dense.add(0, 1, 70_000), 70_000);
+        assert_eq!(dense.bytes_per_cell(), 4);
+        assert_eq!(
+            dense.add(0, 1, u32::MAX as usize),
+            70_000 + u32::MAX as usize
+        );
+        assert_eq!(dense.bytes_per_cell(), std::mem::size_of::<usize>());
+        assert_eq!(dense.decrement(1, 2), 255);
+
+        let mut cells: Vec<_> = dense.iter_nonzero().collect();
+        cells.sort();
+        assert_eq!(
+            cells,
+            vec![(0, 0, 1), (0, 1, 70_000 + u32::MAX as usize), (1, 2, 255)]
+        );
+    }
+
+    #[test]
+    fn test_backend_choose() {
+        assert_eq!(Backend::choose(1000, 1000, 200_000), Backend::Dense);
+        assert_eq!(Backend::choose(10, 10, 1), Backend::Dense);
+        assert_eq!(Backend::choose(1_000_000, 1_000_000, 10), Backend::Sparse);
+        assert_eq!(Backend::choose(usize::MAX, 2, 10), Backend::Sparse);
+        assert_eq!(Backend::choose(usize::MAX, 1, usize::MAX), Backend::Sparse);
+        assert_eq!(Backend::choose(1 << 20, 1 << 20, 1 << 40), Backend::Sparse);
+        assert!(Backend::Dense.fits(1 << 14, 1 << 14));
+        assert!(!Backend::Dense.fits(1 << 20, 1 << 20));
+        assert!(!Backend::Dense.fits(usize::MAX, 2));
+        assert!(Backend::Sparse.fits(usize::MAX, usize::MAX));
+    }
+}
@@ -0,0 +1,174 @@
+/// This file draws the vent lines and their overlap points as an SVG
+/// image that can be opened in a browser.
+///
+/// Every line from the PointsData is drawn as an SVG line colored by its
+/// orientation, and every Grid cell at or above the threshold is drawn
+/// as a circle sized by its count. Cell x,y is centered on the point x,y
+/// of the SVG coordinate system, so the view box can crop to any Rect.
+use std::io::{self, Write};
+
+use crate::common::Rect;
+use crate::file::PointsData;
+use crate::grid::Grid;
+use crate::line::Orientation;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SvgOptions {
+    /// Cells to show, None to show the whole grid.
+    pub view_box: Option<Rect>,
+    pub horizontal_color: String,
+    pub vertical_color: String,
+    pub diagonal_color: String,
+    pub marker_color: String,
+    /// Cells with at least this count get a marker.
+    pub threshold: usize,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            view_box: None,
+            horizontal_color: String::from("steelblue"),
+            vertical_color: String::from("seagreen"),
+            diagonal_color: String::from("darkorange"),
+            marker_color: String::from("crimson"),
+            threshold: 2,
+        }
+    }
+}
+
+impl SvgOptions {
+    fn line_color(&self, orientation: Orientation) -> &str {
+        match orientation {
+            Orientation::Horizontal => &self.horizontal_color,
+            Orientation::Vertical => &self.vertical_color,
+            Orientation::Diagonal | Orientation::Other => &self.diagonal_color,
+        }
+    }
+}
+
+/// Radius of the marker for a count, growing with the count up to half a cell.
+fn marker_radius(count: usize) -> f64 {
+    (0.15 + 0.05 * count as f64).min(0.5)
+}
+
+/// Writes the lines allowed by the line mode of the points and the
+/// overlap markers of the grid as an SVG document.
+pub fn write_svg(
+    points: &PointsData,
+    grid: &Grid,
+    options: &SvgOptions,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let view_box = options.view_box.unwrap_or(grid.bounds());
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        view_box.min.x as f64 - 0.5,
+        view_box.min.y as f64 - 0.5,
+        view_box.width(),
+        view_box.height(),
+        view_box.width().max(100),
+        view_box.height().max(100),
+    )?;
+
+    writeln!(writer, r#"<g stroke-width="0.2" stroke-linecap="round">"#)?;
+    for (start, end) in points
+        .iter()
+        .filter(|(start, end)| points.line_mode.allows(start, end))
+        .filter(|(start, end)| view_box.intersects(&Rect::new(*start, *end)))
+    {
+        writeln!(
+            writer,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}"/>"#,
+            start.x,
+            start.y,
+            end.x,
+            end.y,
+            options.line_color(Orientation::of(start, end)),
+        )?;
+    }
+    writeln!(writer, "</g>")?;
+
+    let mut markers: Vec<_> = grid
+        .point_counts()
+        .filter(|point_count| point_count.count.0 >= options.threshold)
+        .filter(|point_count| view_box.contains(&point_count.point))
+        .collect();
+    markers.sort_by_key(|point_count| (point_count.point.y, point_count.point.x));
+    writeln!(writer, r#"<g fill="{}">"#, options.marker_color)?;
+    for point_count in markers {
+        writeln!(
+            writer,
+            r#"<circle cx="{}" cy="{}" r="{}"><title>{}</title></circle>"#,
+            point_count.point.x,
+            point_count.point.y,
+            marker_radius(point_count.count.0),
+            point_count,
+        )?;
+    }
+    writeln!(writer, "</g>")?;
+    writeln!(writer, "</svg>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::Coordinate;
+    use crate::line::LineMode;
+
+    fn points() -> PointsData {
+        PointsData {
+            point_pairs: vec![
+                (Coordinate { x: 0, y: 0 }, Coordinate { x: 4, y: 0 }),
+                (Coordinate { x: 1, y: 0 }, Coordinate { x: 1, y: 3 }),
+                (Coordinate { x: 0, y: 0 }, Coordinate { x: 3, y: 3 }),
+            ],
+            indices: vec![0, 1, 2],
+            min_x: 0,
+            min_y: 0,
+            max_x: 4,
+            max_y: 3,
+            line_mode: LineMode::Diagonal,
+        }
+    }
+
+    fn render(options: &SvgOptions) -> String {
+        let grid = Grid::new(points());
+        let mut svg = Vec::new();
+        write_svg(&points(), &grid, options, &mut svg).unwrap();
+        String::from_utf8(svg).unwrap()
+    }
+
+    #[test]
+    fn test_write_svg() {
+        let svg = render(&SvgOptions::default());
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"viewBox="-0.5 -0.5 5 4""#));
+        assert!(svg.contains(r#"<line x1="0" y1="0" x2="4" y2="0" stroke="steelblue"/>"#));
+        assert!(svg.contains(r#"<line x1="1" y1="0" x2="1" y2="3" stroke="seagreen"/>"#));
+        assert!(svg.contains(r#"<line x1="0" y1="0" x2="3" y2="3" stroke="darkorange"/>"#));
+        assert!(svg.contains(
+            r#"<circle cx="0" cy="0" r="0.25"><title>Point (0,0): Count=2</title></circle>"#
+        ));
+        assert!(svg.contains(r#"<circle cx="1" cy="0" r="0.25">"#));
+        assert!(svg.contains(r#"<circle cx="1" cy="1" r="0.25">"#));
+        assert_eq!(svg.matches("<circle").count(), 3);
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_write_svg_view_box() {
+        let options = SvgOptions {
+            view_box: Some(Rect::new(
+                Coordinate { x: 1, y: 1 },
+                Coordinate { x: 3, y: 3 },
+            )),
+            ..SvgOptions::default()
+        };
+        let svg = render(&options);
+        assert!(svg.contains(r#"viewBox="0.5 0.5 3 3""#));
+        assert!(!svg.contains(r#"x2="4" y2="0""#));
+        assert_eq!(svg.matches("<circle").count(), 1);
+    }
+}
@@ -0,0 +1,12 @@
+/// Helpers shared by the unit tests.
+use crate::file::read_to_points;
+use crate::grid::Grid;
+use crate::line::LineMode;
+
+/// The example from the puzzle.
+pub const SAMPLE: &str = include_str!("../data/sample2.txt");
+
+/// Builds a Grid from the sample in the line mode.
+pub fn sample_grid(line_mode: LineMode) -> Grid {
+    Grid::new(read_to_points(SAMPLE.as_bytes(), line_mode).unwrap())
+}
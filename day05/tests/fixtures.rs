@@ -0,0 +1,106 @@
+/// Regression tests that run the samples in data/ through the whole
+/// pipeline and compare the overlap count and the printed Grid with
+/// checked-in expectations.
+///
+/// Expected grids hold what `println!("{}", grid)` prints, the same way
+/// data/solution1.txt was produced. Run with UPDATE_FIXTURES=1 to
+/// rewrite every expectation from the current output instead of
+/// comparing against it. Expectations all live in tests/fixtures, so
+/// that never touches the puzzle answers in data/.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use day05::file::read_file_to_points;
+use day05::grid::Grid;
+use day05::line::LineMode;
+
+struct Fixture {
+    input: &'static str,
+    line_mode: LineMode,
+    count: &'static str,
+    grid: &'static str,
+}
+
+const FIXTURES: [Fixture; 3] = [
+    Fixture {
+        input: "data/sample1.txt",
+        line_mode: LineMode::Orthogonal,
+        count: "tests/fixtures/sample1_orthogonal.count",
+        grid: "tests/fixtures/sample1_orthogonal.grid",
+    },
+    Fixture {
+        input: "data/sample2.txt",
+        line_mode: LineMode::Diagonal,
+        count: "tests/fixtures/sample2_diagonal.count",
+        grid: "tests/fixtures/sample2_diagonal.grid",
+    },
+    Fixture {
+        input: "data/data1.txt",
+        line_mode: LineMode::Orthogonal,
+        count: "tests/fixtures/data1_orthogonal.count",
+        grid: "tests/fixtures/data1_orthogonal.grid",
+    },
+];
+
+fn path(relative: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(relative)
+}
+
+fn updating() -> bool {
+    env::var("UPDATE_FIXTURES").is_ok_and(|value| value == "1")
+}
+
+/// Compares actual with the expected file, or overwrites the file with
+/// actual when regenerating.
+fn check(expected: &str, actual: &str) {
+    let expected_path = path(expected);
+    if updating() {
+        fs::write(&expected_path, actual).unwrap();
+        return;
+    }
+    let expected_text = fs::read_to_string(&expected_path).unwrap_or_else(|err| {
+        panic!(
+            "{}: {} (run with UPDATE_FIXTURES=1 to create it)",
+            expected, err
+        )
+    });
+    assert!(
+        expected_text == actual,
+        "{} is out of date (run with UPDATE_FIXTURES=1 to regenerate it)\n\
+         --- expected\n{}\n--- actual\n{}",
+        expected,
+        expected_text,
+        actual
+    );
+}
+
+fn run_fixture(fixture: &Fixture) {
+    let points = read_file_to_points(path(fixture.input).to_str().unwrap(), fixture.line_mode)
+        .unwrap_or_else(|err| panic!("{}: {}", fixture.input, err));
+    let grid = Grid::new(points);
+    check(fixture.count, &format!("{}\n", grid.sum_double_counts()));
+    check(fixture.grid, &format!("{}\n", grid));
+
+    let expected: Grid = fs::read_to_string(path(fixture.grid))
+        .unwrap()
+        .parse()
+        .unwrap_or_else(|err| panic!("{}: {}", fixture.grid, err));
+    assert_eq!(expected.bounds(), grid.bounds());
+    assert_eq!(expected.sum_double_counts(), grid.sum_double_counts());
+}
+
+#[test]
+fn test_sample1_orthogonal() {
+    run_fixture(&FIXTURES[0]);
+}
+
+#[test]
+fn test_sample2_diagonal() {
+    run_fixture(&FIXTURES[1]);
+}
+
+#[test]
+fn test_data1_orthogonal() {
+    run_fixture(&FIXTURES[2]);
+}
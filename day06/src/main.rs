@@ -1,61 +1,297 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::process;
+
+/// The highest internal timer a fish can have, the one of a new fish.
+const MAX_TIMER: usize = 8;
+
+/// The errors reported while loading the fish timers.
+#[derive(Debug)]
+enum TimerError {
+    Io(io::Error),
+    /// The value at the (1-based) position is not a number.
+    InvalidTimer {
+        position: usize,
+        text: String,
+    },
+    /// The timer at the (1-based) position is above MAX_TIMER.
+    OutOfRange {
+        position: usize,
+        timer: usize,
+    },
+}
+
+impl fmt::Display for TimerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimerError::Io(err) => write!(f, "{}", err),
+            TimerError::InvalidTimer { position, text } => {
+                write!(f, "timer {}: invalid number '{}'", position, text)
+            }
+            TimerError::OutOfRange { position, timer } => write!(
+                f,
+                "timer {}: {} is out of range 0..={}",
+                position, timer, MAX_TIMER
+            ),
+        }
+    }
+}
+
+impl Error for TimerError {}
+
+impl From<io::Error> for TimerError {
+    fn from(err: io::Error) -> Self {
+        TimerError::Io(err)
+    }
+}
+
+/// Parses comma-separated timers like "3,4,3,1,2". Surrounding whitespace,
+/// including the trailing newline of a file, is ignored.
+fn parse_timers(input: &str) -> Result<Vec<usize>, TimerError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+    input
+        .split(',')
+        .enumerate()
+        .map(|(index, text)| {
+            let text = text.trim();
+            let timer: usize = text.parse().map_err(|_| TimerError::InvalidTimer {
+                position: index + 1,
+                text: text.to_string(),
+            })?;
+            if timer > MAX_TIMER {
+                return Err(TimerError::OutOfRange {
+                    position: index + 1,
+                    timer,
+                });
+            }
+            Ok(timer)
+        })
+        .collect()
+}
+
+fn read_timers(mut reader: impl Read) -> Result<Vec<usize>, TimerError> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input)?;
+    parse_timers(&input)
+}
+
+/// Reads the timers from the file at path, or from stdin when the path is "-".
+fn load_timers(path: &str) -> Result<Vec<usize>, TimerError> {
+    if path == "-" {
+        read_timers(io::stdin().lock())
+    } else {
+        read_timers(File::open(path)?)
+    }
+}
+
+/// The timer a fish restarts from after creating a new fish.
+const RESET_TIMER: usize = 6;
+
+/// The number of fish with each timer on a given day.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Snapshot {
+    day: usize,
+    /// counts[t] is the number of fish with a timer of t.
+    counts: [u64; MAX_TIMER + 1],
+}
+
+impl Snapshot {
+    fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+}
+
 /// Represents a school of fish.
 /// Each fish has an internal timer which determines its state.
 /// When a fish's timer reaches 0, it creates a new fish with an internal timer of 8.
-/// All fish are contained in a vector.
+/// Fish with the same timer behave the same, so the school only keeps
+/// how many fish have each timer, and the day it has reached.
 struct FishSchool {
-    fish: Vec<usize>,
- }
- 
- impl FishSchool {
-    /// Creates a new FishSchool with the given initial fish.
-    fn new(initial_fish: Vec<usize>) -> Self {
-        FishSchool { fish: initial_fish }
-    }
- 
-    /// Simulates the growth of the fish school over a certain number of days.
-    /// Returns the total number of fish after the simulation.
-    fn simulate_fishes(&mut self, days: usize) -> usize {
-        // Create a map to count the number of fish at each stage of their lifecycle.
-        // Index 0 represents fish with a timer of 0, index 1 represents fish with a timer of 1, etc.
-        let mut map = [0; 9];
-        // Count the number of fish and their life cycle at the start.
-        // It does this by incrementing the corresponding index in the map array for each fish. 
-        // For instance, if the fish vector is:
-        //      [3, 4, 3, 1, 2], 
-        // after this loop, the map array would look like this: 
-        //      [0, 0, 0, 2, 1, 1, 0, 0, 0]. 
-        // This means there are 2 fish with a timer of 3, 1 fish with a timer of 4, and 1 fish with a timer of 1.
-        for &fish in &self.fish {
-            map[fish] += 1;
+    // Index 0 counts the fish with a timer of 0, index 1 the fish with a timer of 1, etc.
+    // For instance, if the initial fish are:
+    //      [3, 4, 3, 1, 2],
+    // the counts look like this:
+    //      [0, 1, 1, 2, 1, 0, 0, 0, 0].
+    counts: [u64; MAX_TIMER + 1],
+    day: usize,
+}
+
+impl FishSchool {
+    /// Creates a new FishSchool on day 0 with the given initial fish.
+    /// Panics if a timer is above MAX_TIMER, load_timers never returns one.
+    fn new(initial_fish: &[usize]) -> Self {
+        let mut counts = [0; MAX_TIMER + 1];
+        for &fish in initial_fish {
+            counts[fish] += 1;
+        }
+        FishSchool { counts, day: 0 }
+    }
+
+    /// Simulates one day.
+    fn step(&mut self) {
+        // All fish move forward one stage in their lifecycle, by shifting the
+        // counts one position to the left. The fish with a timer of 0 wrap
+        // around to index 8: they are the new fish they create.
+        self.counts.rotate_left(1);
+        // The fish that created them start again from 6.
+        self.counts[RESET_TIMER] += self.counts[MAX_TIMER];
+        self.day += 1;
+    }
+
+    /// Simulates the given number of days.
+    fn advance(&mut self, days: usize) {
+        for _ in 0..days {
+            self.step();
+        }
+    }
+
+    /// Returns the number of days simulated so far.
+    fn day(&self) -> usize {
+        self.day
+    }
+
+    /// Returns the total number of fish.
+    fn total(&self) -> u64 {
+        self.snapshot().total()
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            day: self.day,
+            counts: self.counts,
         }
- 
-        // Simulate each day.
-        for _ in 1..days {
-            // Each day, all fish with a timer of 0 create a new fish with a timer of 8.
-            // These new fish are added to the map at index 8.
-            // say after some time, we have a usize array [1,0,0,0,0,0,0,0,0]. 
-            // Here, there is 1 fish with a timer of 0. So, map[0] would be 1. 
-            // The line map[8] += map[0]; now adds the value of map[0] (which is 1) 
-            // to map[8]. So, the array becomes [1,0,0,0,0,0,0,0,1]. 
-            // This means that 1 new fish with a timer of 8 was created.
-            map[8] += map[0];
-            // Then, all fish move forward one stage in their lifecycle.
-            // This is done by shifting all elements in the map one position to the left,
-            // wrapping around from index 0 to index 8.
-            map.rotate_left(1);
+    }
+}
+
+/// Simulates one day at a time, yielding the school after each day.
+/// The iterator never ends, use by_ref to keep the school afterwards.
+impl Iterator for FishSchool {
+    type Item = Snapshot;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.step();
+        Some(self.snapshot())
+    }
+}
+
+fn main() {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| String::from("data/data1.txt"));
+    let timers = match load_timers(&path) {
+        Ok(timers) => timers,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
         }
- 
-        // Sum up the counts in the map to get the total number of fish.
-        map.iter().sum()
-    }
- }
- 
- fn main() {
-    let mut fish = FishSchool::new(vec![
-        // Initial fish population...
-    ]);
+    };
+    let mut school = FishSchool::new(&timers);
     // part 1
-    println!("Part 1: {:?}", fish.simulate_fishes(80));
-    // part 2
-    println!("Part 2: {:?}", fish.simulate_fishes(256))
- }
+    school.advance(80);
+    println!("Part 1: {}", school.total());
+    // part 2, carrying on from day 80
+    school.advance(256 - school.day());
+    println!("Part 2: {}", school.total())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timers() {
+        assert_eq!(parse_timers("3,4,3,1,2\n").unwrap(), vec![3, 4, 3, 1, 2]);
+        assert_eq!(parse_timers("3,4,3,1,2").unwrap(), vec![3, 4, 3, 1, 2]);
+        assert_eq!(parse_timers(" 0, 8 ").unwrap(), vec![0, 8]);
+        assert_eq!(parse_timers("\n").unwrap(), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn test_parse_timers_errors() {
+        assert!(matches!(
+            parse_timers("3,x,1"),
+            Err(TimerError::InvalidTimer { position: 2, text }) if text == "x"
+        ));
+        assert!(matches!(
+            parse_timers("3,,1"),
+            Err(TimerError::InvalidTimer { position: 2, text }) if text.is_empty()
+        ));
+        assert!(matches!(
+            parse_timers("3,-1"),
+            Err(TimerError::InvalidTimer { position: 2, .. })
+        ));
+        assert!(matches!(
+            parse_timers("3,4,9"),
+            Err(TimerError::OutOfRange {
+                position: 3,
+                timer: 9
+            })
+        ));
+        assert_eq!(
+            parse_timers("13").unwrap_err().to_string(),
+            "timer 1: 13 is out of range 0..=8"
+        );
+    }
+
+    #[test]
+    fn test_read_timers_from_file() {
+        assert_eq!(
+            load_timers("data/sample1.txt").unwrap(),
+            vec![3, 4, 3, 1, 2]
+        );
+        assert!(matches!(
+            load_timers("data/missing.txt"),
+            Err(TimerError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_fish_school_sample() {
+        let mut school = FishSchool::new(&[3, 4, 3, 1, 2]);
+        assert_eq!(school.day(), 0);
+        assert_eq!(school.total(), 5);
+        school.advance(18);
+        assert_eq!(school.total(), 26);
+        school.advance(80 - 18);
+        assert_eq!(school.day(), 80);
+        assert_eq!(school.total(), 5934);
+        school.advance(256 - 80);
+        assert_eq!(school.total(), 26984457539);
+    }
+
+    #[test]
+    fn test_fish_school_snapshots() {
+        let mut school = FishSchool::new(&[3, 4, 3, 1, 2]);
+        let days: Vec<Snapshot> = school.by_ref().take(2).collect();
+        // After 1 day: 2,3,2,0,1
+        assert_eq!(
+            days[0],
+            Snapshot {
+                day: 1,
+                counts: [1, 1, 2, 1, 0, 0, 0, 0, 0]
+            }
+        );
+        // After 2 days: 1,2,1,6,0,8
+        assert_eq!(
+            days[1],
+            Snapshot {
+                day: 2,
+                counts: [1, 2, 1, 0, 0, 0, 1, 0, 1]
+            }
+        );
+        assert_eq!(days[1].total(), 6);
+        assert_eq!(school.day(), 2);
+
+        let mut stepped = FishSchool::new(&[3, 4, 3, 1, 2]);
+        stepped.step();
+        stepped.step();
+        assert_eq!(stepped.snapshot(), days[1]);
+        assert_eq!(school.nth(15).map(|snapshot| snapshot.total()), Some(26));
+    }
+}